@@ -0,0 +1,357 @@
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+use git2::{Cred, CredentialType, ErrorClass, ErrorCode, RemoteCallbacks};
+
+use crate::error::NuanceError;
+
+/// Token variable checked after the host-specific ones, for configured
+/// provider hosts only.
+const GENERIC_TOKEN_ENV: &str = "NUANCE_GIT_TOKEN";
+
+/// Key files tried from `~/.ssh`, in order.
+const DEFAULT_SSH_KEYS: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Authentication settings from the global config.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    /// An SSH private key to try before the ones in `~/.ssh`.
+    pub ssh_key: Option<PathBuf>,
+    /// Token variables for specific hosts, from `[providers.<alias>]`.
+    pub host_tokens: Vec<(String, String)>,
+    /// Hosts that may receive `NUANCE_GIT_TOKEN`.
+    pub token_hosts: Vec<String>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Set the authentication settings for this run.
+pub fn configure(settings: Settings) {
+    let _ = SETTINGS.set(settings);
+}
+
+fn settings() -> Settings {
+    SETTINGS.get().cloned().unwrap_or_default()
+}
+
+/// The credentials offered to a remote so far, for error messages.
+#[derive(Debug, Clone, Default)]
+pub struct Attempts(Rc<RefCell<Vec<String>>>);
+
+impl Attempts {
+    fn record(&self, source: String) {
+        self.0.borrow_mut().push(source);
+    }
+
+    fn tried(&self, source: &str) -> bool {
+        self.0.borrow().iter().any(|s| s == source)
+    }
+
+    fn list(&self) -> Vec<String> {
+        self.0.borrow().clone()
+    }
+}
+
+/// Build remote callbacks that answer credential requests for `url`.
+///
+/// Each source is offered at most once, in this order: ssh-agent, the
+/// configured SSH key and the default keys in `~/.ssh` (for SSH remotes);
+/// a token from the environment and then the git credential helper (for
+/// HTTPS remotes). The returned [`Attempts`] records what was offered.
+pub fn callbacks(url: &str) -> (RemoteCallbacks<'static>, Attempts) {
+    let attempts = Attempts::default();
+    let recorder = attempts.clone();
+    let remote_https = is_https(url);
+    let settings = settings();
+
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username_from_url, allowed| {
+        let user = username_from_url.unwrap_or("git");
+
+        if allowed.contains(CredentialType::USERNAME) {
+            return Cred::username(user);
+        }
+
+        if allowed.contains(CredentialType::SSH_KEY) {
+            if !recorder.tried("ssh-agent") {
+                recorder.record("ssh-agent".to_string());
+                return Cred::ssh_key_from_agent(user);
+            }
+            for key in ssh_keys(&settings) {
+                let label = key.display().to_string();
+                if key.is_file() && !recorder.tried(&label) {
+                    recorder.record(label);
+                    return Cred::ssh_key(user, None, &key, None);
+                }
+            }
+        }
+
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT) {
+            // Judge the URL git is authenticating to, which may differ from
+            // the configured one after a redirect
+            let token =
+                env_token(url, &settings, |var| std::env::var(var).ok()).filter(|_| remote_https);
+            if let Some((var, token)) = token {
+                let label = format!("${var}");
+                if !recorder.tried(&label) {
+                    recorder.record(label);
+                    let token_user = username_from_url
+                        .unwrap_or_else(|| token_username(host_of(url).unwrap_or_default()));
+                    return Cred::userpass_plaintext(token_user, &token);
+                }
+            }
+            if !recorder.tried("git credential helper") {
+                recorder.record("git credential helper".to_string());
+                if let Ok(config) = git2::Config::open_default()
+                    && let Ok(cred) = Cred::credential_helper(&config, url, username_from_url)
+                {
+                    return Ok(cred);
+                }
+            }
+        }
+
+        if allowed.contains(CredentialType::DEFAULT) && !recorder.tried("default") {
+            recorder.record("default".to_string());
+            return Cred::default();
+        }
+
+        Err(git2::Error::from_str("no more credentials to try"))
+    });
+    (callbacks, attempts)
+}
+
+/// Turn a failed git operation on `url` into an error, explaining
+/// authentication failures instead of passing on the raw git error.
+pub fn explain(url: &str, err: git2::Error, attempts: &Attempts) -> NuanceError {
+    if !is_auth_error(&err) {
+        return NuanceError::Git(err);
+    }
+    let tried = attempts
+        .list()
+        .into_iter()
+        .filter(|s| s != "default")
+        .collect::<Vec<_>>();
+    NuanceError::Auth {
+        url: url.to_string(),
+        tried: if tried.is_empty() {
+            "no credentials were found".to_string()
+        } else {
+            format!("tried {}", tried.join(", "))
+        },
+    }
+}
+
+fn is_auth_error(err: &git2::Error) -> bool {
+    if err.code() == ErrorCode::Auth || err.code() == ErrorCode::Certificate {
+        return true;
+    }
+    let message = err.message().to_ascii_lowercase();
+    matches!(
+        err.class(),
+        ErrorClass::Ssh | ErrorClass::Http | ErrorClass::Callback | ErrorClass::Net
+    ) && (message.contains("auth")
+        || message.contains("credentials")
+        || message.contains("401")
+        || message.contains("403"))
+}
+
+/// SSH keys to offer: the configured key first, then the defaults.
+fn ssh_keys(settings: &Settings) -> Vec<PathBuf> {
+    let mut keys: Vec<PathBuf> = settings.ssh_key.iter().cloned().collect();
+    if let Some(home) = dirs::home_dir() {
+        keys.extend(DEFAULT_SSH_KEYS.iter().map(|k| home.join(".ssh").join(k)));
+    }
+    keys
+}
+
+/// Find a token for an HTTPS `url` in the environment, returning the
+/// variable it came from. A provider's own `token_env` comes first, then
+/// the usual variables of well-known hosts, then `NUANCE_GIT_TOKEN` for
+/// configured provider hosts. Other transports never get a token.
+fn env_token<F>(url: &str, settings: &Settings, lookup: F) -> Option<(String, String)>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if !is_https(url) {
+        return None;
+    }
+    let host = host_of(url)?;
+    let host_vars: &[&str] = match host {
+        "github.com" => &["GITHUB_TOKEN", "GH_TOKEN"],
+        "gitlab.com" => &["GITLAB_TOKEN"],
+        "codeberg.org" => &["CODEBERG_TOKEN"],
+        _ => &[],
+    };
+    let generic = settings
+        .token_hosts
+        .iter()
+        .any(|h| h.eq_ignore_ascii_case(host))
+        .then_some(GENERIC_TOKEN_ENV);
+    settings
+        .host_tokens
+        .iter()
+        .filter(|(h, _)| h.eq_ignore_ascii_case(host))
+        .map(|(_, var)| var.as_str())
+        .chain(host_vars.iter().copied())
+        .chain(generic)
+        .find_map(|var| {
+            lookup(var)
+                .filter(|v| !v.is_empty())
+                .map(|v| (var.to_string(), v))
+        })
+}
+
+fn is_https(url: &str) -> bool {
+    url.get(..8)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://"))
+}
+
+/// The username to send with a token over HTTPS.
+fn token_username(host: &str) -> &'static str {
+    match host {
+        "github.com" => "x-access-token",
+        _ => "oauth2",
+    }
+}
+
+/// The host of a git URL, for `https://host/...`, `ssh://user@host/...`
+/// and scp-like `user@host:path` forms.
+pub fn host_of(url: &str) -> Option<&str> {
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest,
+        None if url.contains(':') => url,
+        None => return None,
+    };
+    let authority = rest.split(['/', ':']).next()?;
+    let host = authority.rsplit('@').next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_host_in_url_forms() {
+        assert_eq!(host_of("https://github.com/user/repo"), Some("github.com"));
+        assert_eq!(
+            host_of("ssh://git@gitlab.example.com:2222/team/repo.git"),
+            Some("gitlab.example.com")
+        );
+        assert_eq!(host_of("git@github.com:user/repo.git"), Some("github.com"));
+        assert_eq!(host_of("file:///tmp/repo"), None);
+    }
+
+    #[test]
+    fn picks_tokens_from_environment() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+
+        let settings = Settings {
+            token_hosts: vec!["github.com".to_string(), "git.example.com".to_string()],
+            ..Default::default()
+        };
+        let github = "https://github.com/me/private";
+
+        let found = env_token(github, &settings, env(&[("GH_TOKEN", "gh")]));
+        assert_eq!(found, Some(("GH_TOKEN".to_string(), "gh".to_string())));
+
+        // Host-specific variables win over the generic one
+        let found = env_token(
+            github,
+            &settings,
+            env(&[("GITHUB_TOKEN", "gh"), ("NUANCE_GIT_TOKEN", "any")]),
+        );
+        assert_eq!(found.unwrap().0, "GITHUB_TOKEN");
+        let found = env_token(github, &settings, env(&[("NUANCE_GIT_TOKEN", "any")]));
+        assert_eq!(found.unwrap().0, "NUANCE_GIT_TOKEN");
+
+        assert_eq!(
+            env_token(
+                "https://git.example.com/x",
+                &settings,
+                env(&[("GITHUB_TOKEN", "gh")])
+            ),
+            None
+        );
+        assert_eq!(
+            env_token(
+                "https://gitlab.com/x",
+                &settings,
+                env(&[("GITLAB_TOKEN", "")])
+            ),
+            None
+        );
+
+        // A provider's token variable wins for its host only
+        let settings = Settings {
+            host_tokens: vec![("git.example.com".to_string(), "WORK_TOKEN".to_string())],
+            token_hosts: settings.token_hosts,
+            ..Default::default()
+        };
+        let vars = env(&[("WORK_TOKEN", "work"), ("NUANCE_GIT_TOKEN", "any")]);
+        let found = env_token("https://git.example.com/x", &settings, vars);
+        assert_eq!(found.unwrap().0, "WORK_TOKEN");
+        let found = env_token(github, &settings, vars);
+        assert_eq!(found.unwrap().0, "NUANCE_GIT_TOKEN");
+    }
+
+    #[test]
+    fn never_sends_tokens_to_unknown_hosts() {
+        let settings = Settings {
+            token_hosts: vec!["github.com".to_string()],
+            ..Default::default()
+        };
+        let lookup = |name: &str| (name == "NUANCE_GIT_TOKEN").then(|| "secret".to_string());
+        assert_eq!(
+            env_token("https://evil.example.net/x", &settings, lookup),
+            None
+        );
+    }
+
+    #[test]
+    fn never_sends_tokens_over_plain_http() {
+        let settings = Settings {
+            token_hosts: vec!["github.com".to_string()],
+            ..Default::default()
+        };
+        let lookup = |_: &str| Some("secret".to_string());
+        assert_eq!(env_token("http://github.com/me/x", &settings, lookup), None);
+        assert_eq!(
+            env_token("git@github.com:me/x.git", &settings, lookup),
+            None
+        );
+        assert!(env_token("HTTPS://github.com/me/x", &settings, lookup).is_some());
+    }
+
+    #[test]
+    fn explains_authentication_failures() {
+        let attempts = Attempts::default();
+        attempts.record("ssh-agent".to_string());
+        attempts.record("/home/me/.ssh/id_ed25519".to_string());
+
+        let err = git2::Error::new(ErrorCode::Auth, ErrorClass::Ssh, "authentication required");
+        let message = explain("git@github.com:me/private.git", err, &attempts).to_string();
+        assert!(
+            message.contains("git@github.com:me/private.git"),
+            "{message}"
+        );
+        assert!(
+            message.contains("tried ssh-agent, /home/me/.ssh/id_ed25519"),
+            "{message}"
+        );
+
+        let err = git2::Error::new(ErrorCode::NotFound, ErrorClass::Reference, "no such ref");
+        assert!(matches!(
+            explain("https://example.com/x", err, &attempts),
+            NuanceError::Git(_)
+        ));
+    }
+}
@@ -0,0 +1,390 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use git2::Repository;
+
+use crate::error::{NuanceError, Result};
+use crate::git;
+use crate::lockfile::Lockfile;
+
+/// File inside a cached repo's git directory whose mtime records its last use.
+const LAST_USED_MARKER: &str = "nuance-last-used";
+
+/// The configured cache size limit, as written in the global config.
+static MAX_SIZE: OnceLock<Option<String>> = OnceLock::new();
+
+/// A repository in the git cache.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// The `origin` URL, if the repo can be opened.
+    pub url: Option<String>,
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+/// Set the cache size above which least-recently-used repos are evicted.
+///
+/// The size is only parsed when eviction runs, so a malformed setting
+/// doesn't break commands that never evict.
+pub fn set_max_size(max_size: Option<String>) {
+    let _ = MAX_SIZE.set(max_size);
+}
+
+/// The configured cache size limit in bytes, if any; 0 means unlimited.
+fn max_size() -> Result<Option<u64>> {
+    let Some(size) = MAX_SIZE.get().and_then(Option::as_deref) else {
+        return Ok(None);
+    };
+    let bytes = parse_size(size).ok_or_else(|| {
+        NuanceError::Config(format!(
+            "invalid cache_max_size '{size}'; use a size like \"2G\" or \"500M\""
+        ))
+    })?;
+    Ok((bytes > 0).then_some(bytes))
+}
+
+/// Record that a cached repo was just used.
+pub fn touch(repo_dir: &Path) {
+    let _ = std::fs::write(git_dir(repo_dir).join(LAST_USED_MARKER), "");
+}
+
+/// List every repository in the git cache, sorted by URL.
+pub fn entries() -> Result<Vec<Entry>> {
+    entries_in(&git::cache_dir()?)
+}
+
+fn entries_in(cache: &Path) -> Result<Vec<Entry>> {
+    if !cache.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut entries = Vec::new();
+    for dir in std::fs::read_dir(cache)? {
+        let path = dir?.path();
+        if !path.is_dir() || is_partial(&path) {
+            continue;
+        }
+        let url = Repository::open(&path)
+            .ok()
+            .and_then(|repo| repo.find_remote("origin").ok()?.url().map(str::to_string));
+        let marker = git_dir(&path).join(LAST_USED_MARKER);
+        let last_used = std::fs::metadata(&marker)
+            .or_else(|_| std::fs::metadata(&path))
+            .and_then(|m| m.modified())
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        entries.push(Entry {
+            size: dir_size(&path),
+            path,
+            url,
+            last_used,
+        });
+    }
+    entries.sort_by(|a, b| a.url.cmp(&b.url).then_with(|| a.path.cmp(&b.path)));
+    Ok(entries)
+}
+
+/// Remember a lockfile written by an install, so `nuance cache clean
+/// --unused` knows which repos are still needed.
+pub fn remember_lockfile(lock_path: &Path) -> Result<()> {
+    let registry = known_lockfiles_path()?;
+    let lock_path = lock_path.canonicalize()?;
+    let mut known = known_lockfiles()?;
+    if known.contains(&lock_path) {
+        return Ok(());
+    }
+    known.push(lock_path);
+    if let Some(parent) = registry.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let contents: String = known.iter().map(|p| format!("{}\n", p.display())).collect();
+    std::fs::write(registry, contents)?;
+    Ok(())
+}
+
+/// Lockfiles written by earlier installs that still exist.
+fn known_lockfiles() -> Result<Vec<PathBuf>> {
+    let registry = known_lockfiles_path()?;
+    let Ok(contents) = std::fs::read_to_string(registry) else {
+        return Ok(Vec::new());
+    };
+    Ok(contents
+        .lines()
+        .map(PathBuf::from)
+        .filter(|p| p.exists())
+        .collect())
+}
+
+fn known_lockfiles_path() -> Result<PathBuf> {
+    let cache = git::cache_dir()?;
+    let root = cache.parent().unwrap_or(&cache);
+    Ok(root.join("lockfiles"))
+}
+
+/// Cache directories of every git package in a known lockfile.
+fn repos_in_use() -> Result<HashSet<PathBuf>> {
+    let mut in_use = HashSet::new();
+    for lock_path in known_lockfiles()? {
+        // A lockfile that no longer parses keeps nothing alive
+        let Ok(lockfile) = Lockfile::from_path(&lock_path) else {
+            continue;
+        };
+        for url in lockfile.packages.iter().filter_map(|p| p.git.as_deref()) {
+            in_use.insert(git::cached_repo(url)?);
+        }
+    }
+    Ok(in_use)
+}
+
+/// Remove cached repos: all of them, or with `unused` only those no known
+/// lockfile refers to. Returns the removed entries.
+///
+/// Temporary clones left behind by interrupted runs are removed as well.
+pub fn clean(unused: bool) -> Result<Vec<Entry>> {
+    let cache = git::cache_dir()?;
+    remove_stale_partials(&cache)?;
+    let in_use = if unused {
+        repos_in_use()?
+    } else {
+        HashSet::new()
+    };
+    let mut removed = Vec::new();
+    for entry in entries_in(&cache)? {
+        if in_use.contains(&entry.path) {
+            continue;
+        }
+        let _lock = git::lock_repo(&entry.path)?;
+        remove_entry(&entry.path)?;
+        removed.push(entry);
+    }
+    Ok(removed)
+}
+
+/// Whether `path` is a temporary clone (`<entry>.partial`).
+fn is_partial(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "partial")
+}
+
+/// Remove temporary clones whose entry no process is cloning right now.
+fn remove_stale_partials(cache: &Path) -> Result<()> {
+    if !cache.exists() {
+        return Ok(());
+    }
+    for dir in std::fs::read_dir(cache)? {
+        let path = dir?.path();
+        if !path.is_dir() || !is_partial(&path) {
+            continue;
+        }
+        let entry = path.with_extension("");
+        let Some(_lock) = git::try_lock_repo(&entry)? else {
+            continue;
+        };
+        std::fs::remove_dir_all(&path)?;
+        if !entry.exists() {
+            // Best effort: open files can't be removed on every platform
+            let _ = std::fs::remove_file(entry.with_extension("lock"));
+        }
+    }
+    Ok(())
+}
+
+/// Remove a cached repo and its lock file. The caller must hold the lock.
+fn remove_entry(repo_dir: &Path) -> Result<()> {
+    std::fs::remove_dir_all(repo_dir)?;
+    // Best effort: open files can't be removed on every platform
+    let _ = std::fs::remove_file(repo_dir.with_extension("lock"));
+    Ok(())
+}
+
+/// Run `git gc` in every cached repo. Returns each entry with its size
+/// after collection.
+///
+/// libgit2 can't repack, so this needs the `git` command on `PATH`.
+pub fn gc() -> Result<Vec<(Entry, u64)>> {
+    let mut collected = Vec::new();
+    for entry in entries()? {
+        let _lock = git::lock_repo(&entry.path)?;
+        let status = std::process::Command::new("git")
+            .args(["gc", "--quiet"])
+            .current_dir(&entry.path)
+            .status()
+            .map_err(|e| match e.kind() {
+                std::io::ErrorKind::NotFound => NuanceError::Other(
+                    "`nuance cache gc` needs the `git` command, which was not found on PATH"
+                        .to_string(),
+                ),
+                _ => NuanceError::Other(format!("failed to run `git gc`: {e}")),
+            })?;
+        if !status.success() {
+            return Err(NuanceError::Other(format!(
+                "`git gc` failed in {}",
+                entry.path.display()
+            )));
+        }
+        let size = dir_size(&entry.path);
+        collected.push((entry, size));
+    }
+    Ok(collected)
+}
+
+/// Evict least-recently-used repos until the cache fits the configured
+/// size limit. Repos in `keep` (used by the current run) are never evicted.
+pub fn enforce_limit(keep: &[&str]) -> Result<Vec<Entry>> {
+    let Some(max_size) = max_size()? else {
+        return Ok(Vec::new());
+    };
+    let keep: HashSet<PathBuf> = keep
+        .iter()
+        .map(|url| git::cached_repo(url))
+        .collect::<Result<_>>()?;
+
+    let mut entries = entries()?;
+    let mut total: u64 = entries.iter().map(|e| e.size).sum();
+    entries.sort_by_key(|e| e.last_used);
+
+    let mut evicted = Vec::new();
+    for entry in entries {
+        if total <= max_size {
+            break;
+        }
+        if keep.contains(&entry.path) {
+            continue;
+        }
+        // Skip repos another nuance process is using right now
+        let Some(_lock) = git::try_lock_repo(&entry.path)? else {
+            continue;
+        };
+        remove_entry(&entry.path)?;
+        total -= entry.size;
+        evicted.push(entry);
+    }
+    Ok(evicted)
+}
+
+fn git_dir(repo_dir: &Path) -> PathBuf {
+    let dot_git = repo_dir.join(".git");
+    if dot_git.is_dir() {
+        dot_git
+    } else {
+        repo_dir.to_path_buf()
+    }
+}
+
+fn dir_size(path: &Path) -> u64 {
+    walkdir::WalkDir::new(path)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter_map(|e| e.metadata().ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// Parse a size like `500M`, `2GB`, `1.5GiB` or a plain number of bytes.
+/// Units are powers of 1024.
+pub fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Format a byte count for humans, e.g. `12.3 MiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Describe how long ago `time` was, e.g. `3 days ago`.
+pub fn format_age(time: SystemTime) -> String {
+    let elapsed = SystemTime::now()
+        .duration_since(time)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    let (count, unit) = match elapsed {
+        0..60 => return "just now".to_string(),
+        60..3600 => (elapsed / 60, "minute"),
+        3600..86400 => (elapsed / 3600, "hour"),
+        _ => (elapsed / 86400, "day"),
+    };
+    format!("{count} {unit}{} ago", if count == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+
+    #[test]
+    fn parses_and_formats_sizes() {
+        assert_eq!(parse_size("2048"), Some(2048));
+        assert_eq!(parse_size("500M"), Some(500 << 20));
+        assert_eq!(parse_size("2 GB"), Some(2 << 30));
+        assert_eq!(parse_size("1.5GiB"), Some(3 << 29));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size("5 parsecs"), None);
+
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 << 30), "3.0 GiB");
+    }
+
+    #[test]
+    fn formats_ages() {
+        let ago = |secs| SystemTime::now() - Duration::from_secs(secs);
+        assert_eq!(format_age(ago(5)), "just now");
+        assert_eq!(format_age(ago(60)), "1 minute ago");
+        assert_eq!(format_age(ago(3 * 86400 + 10)), "3 days ago");
+    }
+
+    #[test]
+    fn skips_and_removes_partial_clones() {
+        let cache = temp_dir("cache_partial");
+        let repo = cache.join("user_repo-0123456789abcdef");
+        let busy = cache.join("user_busy-0123456789abcdef");
+        Repository::init_bare(&repo).unwrap();
+        std::fs::create_dir_all(repo.with_extension("partial")).unwrap();
+        std::fs::create_dir_all(busy.with_extension("partial")).unwrap();
+
+        let listed = entries_in(&cache).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].path, repo);
+
+        // A clone still in progress holds its entry's lock
+        let cloning = git::lock_repo(&busy).unwrap();
+        remove_stale_partials(&cache).unwrap();
+        assert!(!repo.with_extension("partial").exists());
+        assert!(busy.with_extension("partial").exists());
+        drop(cloning);
+
+        let lock = git::lock_repo(&repo).unwrap();
+        remove_entry(&repo).unwrap();
+        drop(lock);
+        assert!(!repo.exists());
+        assert!(!repo.with_extension("lock").exists());
+
+        let _ = std::fs::remove_dir_all(&cache);
+    }
+}
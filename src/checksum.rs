@@ -1,5 +1,5 @@
 use sha2::{Digest, Sha256};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 use crate::error::Result;
@@ -8,12 +8,14 @@ use crate::error::Result;
 ///
 /// Walks all files in sorted order and hashes each file's relative path
 /// concatenated with its contents, producing a single hex digest.
+/// `.git` directories (present in linked path dependencies) are skipped.
 pub fn hash_directory(dir: &Path) -> Result<String> {
     let mut hasher = Sha256::new();
 
     // Collect and sort all file paths for determinism
     let mut entries: Vec<_> = WalkDir::new(dir)
         .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_file())
         .collect();
@@ -33,6 +35,21 @@ pub fn hash_directory(dir: &Path) -> Result<String> {
     Ok(hex::encode(hasher.finalize()))
 }
 
+/// Compute the same digest as [`hash_directory`] from in-memory files,
+/// given as (relative path, contents) pairs.
+pub fn hash_files(mut files: Vec<(PathBuf, Vec<u8>)>) -> String {
+    let mut hasher = Sha256::new();
+
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (rel_path, contents) in files {
+        hasher.update(rel_path.to_string_lossy().as_bytes());
+        hasher.update(&contents);
+    }
+
+    hex::encode(hasher.finalize())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,9 +1,21 @@
 use clap::{Parser, Subcommand};
 
+use crate::graph::Format;
+
 /// nuance — A module manager for Nushell
 #[derive(Parser, Debug)]
 #[command(name = "nuance", version, about = "A module manager for Nushell")]
 pub struct Cli {
+    /// Number of repositories to fetch and export at once (defaults to the
+    /// `jobs` config setting, then the number of CPUs)
+    #[arg(short = 'j', long, global = true, value_name = "N")]
+    pub jobs: Option<usize>,
+
+    /// Use only the local git cache; never touch the network (also
+    /// `NUANCE_OFFLINE=1` or `offline = true` in the global config)
+    #[arg(long, global = true)]
+    pub offline: bool,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -34,10 +46,62 @@ pub enum Commands {
         /// Use lockfile only; error if missing or stale
         #[arg(long)]
         frozen: bool,
+
+        /// Skip dev-dependencies (production-style install)
+        #[arg(long, conflicts_with = "global")]
+        no_dev: bool,
+    },
+
+    /// Re-resolve dependencies, or only the named packages
+    Update {
+        /// Update global dependencies instead of the local project
+        #[arg(short = 'g', long)]
+        global: bool,
+
+        /// Packages to update (all when omitted)
+        names: Vec<String>,
+
+        /// Also update the dependencies of the named packages
+        #[arg(long, requires = "names")]
+        recursive: bool,
+
+        /// Move the named package to exactly this tag or commit
+        #[arg(long, value_name = "TAG|REV", requires = "names")]
+        precise: Option<String>,
+    },
+
+    /// List dependencies with newer tags or commits available
+    Outdated {
+        /// Check global dependencies instead of the local project
+        #[arg(short = 'g', long)]
+        global: bool,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Exit with status 1 when any dependency is outdated
+        #[arg(long)]
+        exit_code: bool,
     },
 
-    /// Re-resolve all dependencies (ignore existing lockfile)
-    Update,
+    /// Bump tag pins and version requirements to the latest releases
+    Upgrade {
+        /// Upgrade global dependencies instead of the local project
+        #[arg(short = 'g', long)]
+        global: bool,
+
+        /// Packages to upgrade (all tag and version dependencies when omitted)
+        names: Vec<String>,
+
+        /// Stay within the current major version
+        #[arg(long)]
+        compatible: bool,
+
+        /// Print the planned changes without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 
     /// Add a package from a git URL or owner/repo shorthand
     Add {
@@ -45,7 +109,12 @@ pub enum Commands {
         #[arg(short = 'g', long)]
         global: bool,
 
-        /// Git URL (e.g. https://github.com/user/nu-module) or owner/repo shorthand
+        /// Add to [dev-dependencies] instead of [dependencies]
+        #[arg(long, conflicts_with = "global")]
+        dev: bool,
+
+        /// Git URL (e.g. https://github.com/user/nu-module), owner/repo shorthand,
+        /// or provider:owner/repo for a provider from the global config
         url: String,
 
         /// Pin to a specific tag
@@ -59,6 +128,14 @@ pub enum Commands {
         /// Track a branch
         #[arg(long)]
         branch: Option<String>,
+
+        /// Require a semver range matched against tags (e.g. "^1.2")
+        #[arg(long = "version", value_name = "REQ")]
+        version_req: Option<String>,
+
+        /// Allow pre-release tags when auto-detecting the latest version
+        #[arg(long)]
+        pre: bool,
     },
 
     /// Remove a package from mod.toml and .nu_modules/
@@ -71,10 +148,64 @@ pub enum Commands {
         name: String,
     },
 
+    /// Show the dependency tree
+    Tree {
+        /// Show global dependencies instead of the local project
+        #[arg(short = 'g', long)]
+        global: bool,
+
+        /// Start the tree at this package instead of the project
+        package: Option<String>,
+
+        /// Maximum depth to display
+        #[arg(long)]
+        depth: Option<usize>,
+
+        /// Show which packages depend on each package
+        #[arg(long)]
+        invert: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: Format,
+    },
+
+    /// Show every path from the project to a package
+    Why {
+        /// Inspect global dependencies instead of the local project
+        #[arg(short = 'g', long)]
+        global: bool,
+
+        /// Package to explain
+        name: String,
+    },
+
+    /// Inspect and clean the shared git cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
     /// Print the Nushell env_change hook for auto-activating nuance projects
     Hook,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// List cached repositories with their size and last use
+    List,
+
+    /// Remove cached repositories
+    Clean {
+        /// Only remove repositories no known lockfile refers to
+        #[arg(long)]
+        unused: bool,
+    },
+
+    /// Run `git gc` on every cached repository
+    Gc,
+}
+
 pub fn parse() -> Cli {
     Cli::parse()
 }
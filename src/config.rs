@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::{Path, PathBuf};
 
+use crate::auth;
+use crate::document::Document;
 use crate::error::{NuanceError, Result};
 use crate::manifest::DependencySpec;
 
@@ -43,6 +45,58 @@ fn normalize_provider_base_url(provider: &str) -> Option<String> {
     None
 }
 
+/// How to reach repositories on a provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+    #[default]
+    Https,
+    Ssh,
+}
+
+/// A named git host from a `[providers.<alias>]` table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provider {
+    /// e.g. `https://gitlab.example.com`
+    pub base_url: String,
+    /// Environment variable holding an access token for this host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+    #[serde(default)]
+    pub protocol: Protocol,
+}
+
+impl Provider {
+    fn from_base_url(base_url: String) -> Self {
+        Self {
+            base_url,
+            token_env: None,
+            protocol: Protocol::Https,
+        }
+    }
+
+    /// The host name of this provider's base URL.
+    pub fn host(&self) -> Option<&str> {
+        auth::host_of(&self.base_url)
+    }
+
+    /// The clone URL for an `owner/repo` path on this provider.
+    pub fn repo_url(&self, path: &str) -> Result<String> {
+        match self.protocol {
+            Protocol::Https => Ok(format!("{}/{path}", self.base_url.trim_end_matches('/'))),
+            Protocol::Ssh => {
+                let host = self.host().ok_or_else(|| {
+                    NuanceError::Config(format!(
+                        "provider base_url '{}' has no host to connect to over ssh",
+                        self.base_url
+                    ))
+                })?;
+                Ok(format!("git@{host}:{path}.git"))
+            }
+        }
+    }
+}
+
 /// The global nuance config file: `~/.config/nuance/config.toml`.
 ///
 /// Tracks globally-installed modules and optional path overrides.
@@ -54,6 +108,27 @@ pub struct GlobalConfig {
     #[serde(default = "default_git_provider")]
     pub default_git_provider: String,
 
+    /// How many repositories to fetch and export at once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jobs: Option<usize>,
+
+    /// Work from the git cache only, as if `--offline` were always given.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
+
+    /// An SSH private key to try for git remotes, before the ones in `~/.ssh`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key: Option<String>,
+
+    /// Evict least-recently-used repos once the git cache grows past this
+    /// size, e.g. `"2G"` or `"500M"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_max_size: Option<String>,
+
+    /// Named git hosts usable as `<alias>:owner/repo` shorthand.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub providers: BTreeMap<String, Provider>,
+
     #[serde(default)]
     pub dependencies: HashMap<String, DependencySpec>,
 }
@@ -63,6 +138,11 @@ impl Default for GlobalConfig {
         Self {
             modules_dir: None,
             default_git_provider: default_git_provider(),
+            jobs: None,
+            offline: false,
+            ssh_key: None,
+            cache_max_size: None,
+            providers: BTreeMap::new(),
             dependencies: HashMap::new(),
         }
     }
@@ -94,13 +174,16 @@ impl GlobalConfig {
     }
 
     fn load_from_path(path: &Path) -> Result<Self> {
-        let content = std::fs::read_to_string(&path)?;
+        let content = std::fs::read_to_string(path)?;
         let config: GlobalConfig = toml::from_str(&content)
             .map_err(|e| NuanceError::Config(format!("failed to parse {}: {e}", path.display())))?;
         Ok(config)
     }
 
     /// Save the global config back to disk.
+    ///
+    /// The existing file is edited in place, so comments, formatting and
+    /// unrecognized keys survive.
     pub fn save(&self) -> Result<()> {
         let path = global_config_path()?;
 
@@ -108,9 +191,33 @@ impl GlobalConfig {
             std::fs::create_dir_all(parent)?;
         }
 
-        let content = toml::to_string_pretty(self)
-            .map_err(|e| NuanceError::Config(format!("failed to serialize config: {e}")))?;
-        std::fs::write(&path, content)?;
+        let mut doc = if path.exists() {
+            Document::from_path(&path).map_err(|e| {
+                NuanceError::Config(format!("failed to parse {}: {e}", path.display()))
+            })?
+        } else {
+            Document::parse("")?
+        };
+        self.apply_to(&mut doc)?;
+        doc.write_to(&path)
+    }
+
+    /// Write this config's values into `doc`, leaving unchanged entries alone.
+    fn apply_to(&self, doc: &mut Document) -> Result<()> {
+        doc.set_top_level("modules_dir", self.modules_dir.as_deref());
+        doc.set_top_level("default_git_provider", Some(&self.default_git_provider));
+
+        for name in doc.dependency_names("dependencies") {
+            if !self.dependencies.contains_key(&name) {
+                doc.remove_dependency("dependencies", &name);
+            }
+        }
+
+        let mut names: Vec<_> = self.dependencies.keys().collect();
+        names.sort();
+        for name in names {
+            doc.set_dependency("dependencies", name, &self.dependencies[name])?;
+        }
         Ok(())
     }
 
@@ -126,8 +233,64 @@ impl GlobalConfig {
         }
     }
 
+    /// The configured SSH key, with a leading `~` expanded.
+    pub fn ssh_key_path(&self) -> Option<PathBuf> {
+        let key = self.ssh_key.as_deref()?;
+        match (key.strip_prefix("~/"), dirs::home_dir()) {
+            (Some(rest), Some(home)) => Some(home.join(rest)),
+            _ => Some(PathBuf::from(key)),
+        }
+    }
+
+    /// Look up a provider by alias: a `[providers.<alias>]` table first,
+    /// then the built-in aliases and custom hosts.
+    pub fn provider(&self, alias: &str) -> Result<Provider> {
+        if let Some(provider) = self.providers.get(alias) {
+            return Ok(provider.clone());
+        }
+        normalize_provider_base_url(alias)
+            .map(Provider::from_base_url)
+            .ok_or_else(|| {
+                NuanceError::Config(format!(
+                    "unknown git provider '{alias}'; add a [providers.{alias}] table to the global config"
+                ))
+            })
+    }
+
+    /// The provider used for plain `owner/repo` shorthand.
+    pub fn default_provider(&self) -> Result<Provider> {
+        match self.providers.get(&self.default_git_provider) {
+            Some(provider) => Ok(provider.clone()),
+            None => self
+                .default_git_provider_base_url()
+                .map(Provider::from_base_url),
+        }
+    }
+
+    /// Hosts of configured providers that name a token variable.
+    pub fn provider_tokens(&self) -> Vec<(String, String)> {
+        self.providers
+            .values()
+            .filter_map(|p| Some((p.host()?.to_string(), p.token_env.clone()?)))
+            .collect()
+    }
+
+    /// Hosts of the configured providers and of the default provider, the
+    /// only hosts `NUANCE_GIT_TOKEN` is sent to.
+    pub fn provider_hosts(&self) -> Vec<String> {
+        let default = self.default_provider().ok();
+        self.providers
+            .values()
+            .chain(default.as_ref())
+            .filter_map(|p| p.host().map(str::to_string))
+            .collect()
+    }
+
     /// Resolve the configured default git provider to a base URL.
     pub fn default_git_provider_base_url(&self) -> Result<String> {
+        if let Some(provider) = self.providers.get(&self.default_git_provider) {
+            return Ok(provider.base_url.clone());
+        }
         normalize_provider_base_url(&self.default_git_provider).ok_or_else(|| {
             NuanceError::Config(format!(
                 "unsupported default_git_provider '{}'; use one of github, gitlab, codeberg, bitbucket, or a custom host like git.example.com",
@@ -177,12 +340,12 @@ mod tests {
             dependencies: HashMap::from([(
                 "nu-utils".to_string(),
                 DependencySpec {
-                    git: "https://github.com/user/nu-utils".to_string(),
+                    git: Some("https://github.com/user/nu-utils".to_string()),
                     tag: Some("v1.0.0".to_string()),
-                    rev: None,
-                    branch: None,
+                    ..Default::default()
                 },
             )]),
+            ..Default::default()
         };
 
         let serialized = toml::to_string_pretty(&config).unwrap();
@@ -200,6 +363,7 @@ mod tests {
             modules_dir: Some("/custom/path".to_string()),
             default_git_provider: "gitlab".to_string(),
             dependencies: HashMap::new(),
+            ..Default::default()
         };
 
         let serialized = toml::to_string_pretty(&config).unwrap();
@@ -209,12 +373,46 @@ mod tests {
         assert_eq!(parsed.default_git_provider, "gitlab");
     }
 
+    #[test]
+    fn apply_to_preserves_comments_and_unknown_keys() {
+        let original = r#"# my nuance config
+default_git_provider = "github" # where shorthand goes
+experimental = true
+
+[dependencies]
+# shared helpers
+nu-utils = { git = "https://github.com/user/nu-utils", tag = "v1.0.0" }
+nu-old = { git = "https://github.com/user/nu-old", branch = "main" }
+"#;
+        let mut doc = Document::parse(original).unwrap();
+        let mut config: GlobalConfig = toml::from_str(original).unwrap();
+        config.dependencies.remove("nu-old");
+        config.dependencies.insert(
+            "nu-new".to_string(),
+            DependencySpec {
+                git: Some("https://github.com/user/nu-new".to_string()),
+                tag: Some("v0.1.0".to_string()),
+                ..Default::default()
+            },
+        );
+        config.apply_to(&mut doc).unwrap();
+
+        let out = doc.to_string();
+        assert!(out.starts_with("# my nuance config\n"));
+        assert!(out.contains("# where shorthand goes"));
+        assert!(out.contains("experimental = true"));
+        assert!(out.contains("# shared helpers\nnu-utils ="));
+        assert!(!out.contains("nu-old"));
+        assert!(out.contains("nu-new = {"));
+    }
+
     #[test]
     fn modules_dir_custom() {
         let config = GlobalConfig {
             modules_dir: Some("/custom/modules".to_string()),
             default_git_provider: "github".to_string(),
             dependencies: HashMap::new(),
+            ..Default::default()
         };
         assert_eq!(
             config.modules_dir().unwrap(),
@@ -228,6 +426,7 @@ mod tests {
             modules_dir: None,
             default_git_provider: "github".to_string(),
             dependencies: HashMap::new(),
+            ..Default::default()
         };
         let dir = config.modules_dir().unwrap();
         // Should end with nushell/vendor/nuance_modules
@@ -279,6 +478,7 @@ modules_dir = "/tmp/nuance-modules"
             modules_dir: None,
             default_git_provider: "git.example.com".to_string(),
             dependencies: HashMap::new(),
+            ..Default::default()
         };
         assert_eq!(
             config.default_git_provider_base_url().unwrap(),
@@ -292,8 +492,57 @@ modules_dir = "/tmp/nuance-modules"
             modules_dir: None,
             default_git_provider: "not-a-provider".to_string(),
             dependencies: HashMap::new(),
+            ..Default::default()
         };
         let err = config.default_git_provider_base_url().unwrap_err();
         assert!(err.to_string().contains("unsupported default_git_provider"));
     }
+
+    #[test]
+    fn parses_named_providers() {
+        let config: GlobalConfig = toml::from_str(
+            r#"
+default_git_provider = "work"
+
+[providers.work]
+base_url = "https://gitlab.example.com/"
+token_env = "WORK_GITLAB_TOKEN"
+
+[providers.mirror]
+base_url = "https://git.example.org"
+protocol = "ssh"
+"#,
+        )
+        .unwrap();
+
+        let work = config.default_provider().unwrap();
+        assert_eq!(work.protocol, Protocol::Https);
+        assert_eq!(
+            work.repo_url("team/repo").unwrap(),
+            "https://gitlab.example.com/team/repo"
+        );
+        assert_eq!(
+            config
+                .provider("mirror")
+                .unwrap()
+                .repo_url("team/repo")
+                .unwrap(),
+            "git@git.example.org:team/repo.git"
+        );
+        assert_eq!(
+            config.provider_tokens(),
+            vec![(
+                "gitlab.example.com".to_string(),
+                "WORK_GITLAB_TOKEN".to_string()
+            )]
+        );
+        assert_eq!(
+            config.provider_hosts(),
+            vec![
+                "git.example.org",
+                "gitlab.example.com",
+                "gitlab.example.com"
+            ]
+        );
+    }
 }
@@ -0,0 +1,227 @@
+use std::path::Path;
+
+use toml_edit::{DocumentMut, InlineTable, Item, Table, TableLike};
+
+use crate::error::{NuanceError, Result};
+use crate::manifest::DependencySpec;
+
+/// A TOML file (mod.toml or the global config) edited in place.
+///
+/// Unlike re-serializing a parsed struct, edits through this type keep
+/// comments, formatting, key order and any keys nuance doesn't know about.
+#[derive(Debug, Clone)]
+pub struct Document {
+    doc: DocumentMut,
+}
+
+impl Document {
+    /// Read a TOML document from disk.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::parse(&content)
+    }
+
+    /// Parse a TOML document from a string.
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(Self { doc: s.parse()? })
+    }
+
+    /// Write the document back to disk.
+    pub fn write_to(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.doc.to_string())?;
+        Ok(())
+    }
+
+    /// Get the dependency names currently in `table`.
+    pub fn dependency_names(&self, table: &str) -> Vec<String> {
+        self.doc
+            .get(table)
+            .and_then(Item::as_table_like)
+            .map(|t| t.iter().map(|(k, _)| k.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Insert or update a dependency in `table` (e.g. `dependencies`).
+    ///
+    /// New entries are appended as inline tables. Existing entries are
+    /// updated key by key, leaving unknown keys and formatting alone.
+    pub fn set_dependency(&mut self, table: &str, name: &str, spec: &DependencySpec) -> Result<()> {
+        let deps = self.table_mut(table)?;
+
+        match deps.get_mut(name) {
+            Some(item) if item.is_table_like() => {
+                let existing = item.as_table_like_mut().expect("checked table-like");
+                let mut inserted = false;
+                for (key, value) in spec.entries() {
+                    match value {
+                        Some(value) => inserted |= set_str(existing, key, value),
+                        None => {
+                            existing.remove(key);
+                        }
+                    }
+                }
+                // Inline tables can't hold comments, so re-spacing them is lossless
+                if inserted && let Some(inline) = item.as_inline_table_mut() {
+                    inline.fmt();
+                }
+            }
+            _ => {
+                let mut inline = InlineTable::new();
+                for (key, value) in spec.entries() {
+                    if let Some(value) = value {
+                        inline.insert(key, value.into());
+                    }
+                }
+                inline.fmt();
+                deps.insert(name, Item::Value(inline.into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a dependency from `table`. Returns whether it was present.
+    pub fn remove_dependency(&mut self, table: &str, name: &str) -> bool {
+        self.doc
+            .get_mut(table)
+            .and_then(Item::as_table_like_mut)
+            .and_then(|t| t.remove(name))
+            .is_some()
+    }
+
+    /// Set (or with `None`, remove) a top-level string key.
+    pub fn set_top_level(&mut self, key: &str, value: Option<&str>) {
+        let root = self.doc.as_table_mut();
+        match value {
+            Some(value) => {
+                set_str(root, key, value);
+            }
+            None => {
+                root.remove(key);
+            }
+        }
+    }
+
+    /// Get the table-like item named `table`, creating a `[table]` section if missing.
+    fn table_mut(&mut self, table: &str) -> Result<&mut dyn TableLike> {
+        let item = self
+            .doc
+            .as_table_mut()
+            .entry(table)
+            .or_insert_with(|| Item::Table(Table::new()));
+        item.as_table_like_mut()
+            .ok_or_else(|| NuanceError::Other(format!("'{table}' is not a table")))
+    }
+}
+
+impl std::fmt::Display for Document {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+/// Set a string value, keeping the existing value's surrounding whitespace
+/// and comments. Leaves the item untouched if it already has that value.
+///
+/// Returns whether the key was newly inserted.
+fn set_str(table: &mut dyn TableLike, key: &str, value: &str) -> bool {
+    match table.get_mut(key) {
+        Some(item) if item.as_str() == Some(value) => false,
+        Some(item) => {
+            let decor = item.as_value().map(|v| v.decor().clone());
+            *item = toml_edit::value(value);
+            if let (Some(decor), Some(new)) = (decor, item.as_value_mut()) {
+                *new.decor_mut() = decor;
+            }
+            false
+        }
+        None => {
+            table.insert(key, toml_edit::value(value));
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"# Our module
+[package]
+name = "my-module"
+version = "0.1.0"
+
+[dependencies]
+# reviewed by ops
+zeta = { git = "https://github.com/user/zeta", tag = "v1.0.0" } # keep pinned
+alpha = { git = "https://github.com/user/alpha", branch = "main", features = ["x"] }
+
+[tool.custom]
+setting = true
+"#;
+
+    #[test]
+    fn add_dependency_preserves_comments_and_order() {
+        let mut doc = Document::parse(MANIFEST).unwrap();
+        let spec = DependencySpec {
+            git: Some("https://github.com/user/new".to_string()),
+            tag: Some("v0.2.0".to_string()),
+            ..Default::default()
+        };
+        doc.set_dependency("dependencies", "new", &spec).unwrap();
+
+        let out = doc.to_string();
+        assert!(out.starts_with("# Our module\n"));
+        assert!(out.contains("# reviewed by ops\n"));
+        assert!(out.contains("# keep pinned"));
+        assert!(out.contains("[tool.custom]\nsetting = true"));
+        assert!(out.contains(r#"new = { git = "https://github.com/user/new", tag = "v0.2.0" }"#));
+
+        let zeta = out.find("zeta =").unwrap();
+        let alpha = out.find("alpha =").unwrap();
+        let new = out.find("new =").unwrap();
+        assert!(zeta < alpha && alpha < new);
+    }
+
+    #[test]
+    fn update_dependency_keeps_unknown_keys() {
+        let mut doc = Document::parse(MANIFEST).unwrap();
+        let spec = DependencySpec {
+            git: Some("https://github.com/user/alpha".to_string()),
+            tag: Some("v2.0.0".to_string()),
+            ..Default::default()
+        };
+        doc.set_dependency("dependencies", "alpha", &spec).unwrap();
+
+        let out = doc.to_string();
+        assert!(out.contains(
+            r#"alpha = { git = "https://github.com/user/alpha", features = ["x"], tag = "v2.0.0" }"#
+        ));
+    }
+
+    #[test]
+    fn remove_dependency_keeps_the_rest() {
+        let mut doc = Document::parse(MANIFEST).unwrap();
+        assert!(doc.remove_dependency("dependencies", "zeta"));
+        assert!(!doc.remove_dependency("dependencies", "zeta"));
+
+        let out = doc.to_string();
+        assert!(!out.contains("zeta"));
+        assert!(out.contains("alpha ="));
+        assert!(out.contains("[tool.custom]"));
+    }
+
+    #[test]
+    fn creates_missing_dependency_table() {
+        let mut doc = Document::parse("[package]\nname = \"x\"\nversion = \"0.1.0\"\n").unwrap();
+        let spec = DependencySpec {
+            path: Some("../common".to_string()),
+            ..Default::default()
+        };
+        doc.set_dependency("dev-dependencies", "common", &spec)
+            .unwrap();
+
+        let out = doc.to_string();
+        assert!(out.contains("[dev-dependencies]\ncommon = { path = \"../common\" }"));
+        assert_eq!(doc.dependency_names("dev-dependencies"), vec!["common"]);
+    }
+}
@@ -11,13 +11,40 @@ pub enum NuanceError {
     #[error("git error: {0}")]
     Git(#[from] git2::Error),
 
-    #[error("dependency conflict: package '{name}' required at {rev_a} and {rev_b}")]
+    #[error("dependency conflict: '{name}' required at {first}, and at {second}")]
     Conflict {
         name: String,
-        rev_a: String,
-        rev_b: String,
+        first: String,
+        second: String,
     },
 
+    #[error("dependency source conflict: '{name}' required from {first}, and from {second}")]
+    SourceConflict {
+        name: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("no tag of '{name}' matches version requirement '{req}'")]
+    NoMatchingVersion { name: String, req: String },
+
+    #[error("no version of '{name}' satisfies every requirement: {requirements}")]
+    NoSolution { name: String, requirements: String },
+
+    #[error("dependency cycle: {}", path.join(" → "))]
+    Cycle { path: Vec<String> },
+
+    #[error(
+        "cannot continue offline; not in the git cache:\n{}",
+        missing.iter().map(|m| format!("  {m}")).collect::<Vec<_>>().join("\n")
+    )]
+    Offline { missing: Vec<String> },
+
+    #[error(
+        "authentication failed for {url} ({tried}); add your SSH key to ssh-agent, set `ssh_key` in the global config, configure a git credential helper, or set NUANCE_GIT_TOKEN"
+    )]
+    Auth { url: String, tried: String },
+
     #[error("config error: {0}")]
     Config(String),
 
@@ -30,6 +57,9 @@ pub enum NuanceError {
     #[error("toml parse error: {0}")]
     TomlParse(#[from] toml::de::Error),
 
+    #[error("toml parse error: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+
     #[error("toml serialize error: {0}")]
     TomlSerialize(#[from] toml::ser::Error),
 
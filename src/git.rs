@@ -1,8 +1,19 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions, TryLockError};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use git2::{FetchOptions, RemoteCallbacks, Repository, build::RepoBuilder};
+use git2::{AutotagOption, FetchOptions, Repository};
+use sha2::{Digest, Sha256};
 
+use crate::auth;
+use crate::cache;
+use crate::checksum;
 use crate::error::{NuanceError, Result};
+use crate::jobs;
+use crate::progress;
+use crate::version;
 
 /// Returns the global cache directory for git repos: `~/.cache/nuance/git/`.
 pub fn cache_dir() -> Result<PathBuf> {
@@ -11,48 +22,582 @@ pub fn cache_dir() -> Result<PathBuf> {
     Ok(cache.join("nuance").join("git"))
 }
 
-/// Convert a git URL into a safe directory name for caching.
-fn url_to_dirname(url: &str) -> String {
-    url.replace("://", "_")
-        .replace('/', "_")
-        .replace('\\', "_")
-        .replace('.', "_")
+/// Whether to work from the git cache only, without touching the network.
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Turn offline mode on or off for this run.
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Whether offline mode is on.
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+}
+
+/// The cache directory for `url`, whether or not it has been cloned yet.
+pub fn cached_repo(url: &str) -> Result<PathBuf> {
+    Ok(cache_dir()?.join(cache_key(url)))
+}
+
+/// Normalize a git URL so that spellings of the same repo share a cache
+/// entry: surrounding whitespace, trailing slashes and a `.git` suffix are
+/// dropped, and the scheme and host are lowercased.
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    let url = url
+        .strip_suffix(".git")
+        .unwrap_or(url)
+        .trim_end_matches('/');
+
+    // Only the host is case-insensitive; paths and user names are kept as is
+    let lower_host = |authority: &str| match authority.rsplit_once('@') {
+        Some((user, host)) => format!("{user}@{}", host.to_ascii_lowercase()),
+        None => authority.to_ascii_lowercase(),
+    };
+    match url.split_once("://") {
+        Some((scheme, rest)) => {
+            let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+            format!(
+                "{}://{}{path}",
+                scheme.to_ascii_lowercase(),
+                lower_host(authority)
+            )
+        }
+        // scp-like `user@host:path`
+        None => match url.split_once(':') {
+            Some((authority, path)) if !authority.contains(['/', '\\']) => {
+                format!("{}:{path}", lower_host(authority))
+            }
+            _ => url.to_string(),
+        },
+    }
+}
+
+/// The cache directory name for `url`: a readable `owner_repo` prefix plus
+/// a hash of the normalized URL, so distinct URLs never share a directory.
+fn cache_key(url: &str) -> String {
+    let normalized = normalize_url(url);
+    let digest = hex::encode(Sha256::digest(normalized.as_bytes()));
+
+    let path = normalized
+        .rsplit_once("://")
+        .map_or(normalized.as_str(), |(_, rest)| rest);
+    let mut segments = path.rsplit(['/', ':', '\\']).filter(|s| !s.is_empty());
+    let repo = segments.next().unwrap_or_default();
+    let prefix: String = match segments.next() {
+        Some(owner) => format!("{owner}_{repo}"),
+        None => repo.to_string(),
+    }
+    .chars()
+    .map(|c| {
+        if c.is_ascii_alphanumeric() || c == '-' {
+            c
+        } else {
+            '_'
+        }
+    })
+    .take(48)
+    .collect();
+
+    format!("{prefix}-{}", &digest[..16])
+}
+
+/// Lock the cache entry at `repo_dir`, waiting while another nuance
+/// process, or another fetch in this one, holds it. The lock is released when the returned
+/// file is dropped.
+///
+/// Fetching into or removing an entry takes this exclusive lock; reads take
+/// a shared one through [`open_shared`].
+pub fn lock_repo(repo_dir: &Path) -> Result<File> {
+    lock_entry(repo_dir, false)
+}
+
+fn lock_entry(repo_dir: &Path, shared: bool) -> Result<File> {
+    loop {
+        let file = open_lock_file(repo_dir)?;
+        let attempt = if shared {
+            file.try_lock_shared()
+        } else {
+            file.try_lock()
+        };
+        match attempt {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                progress::println(&format!(
+                    "  Waiting for {} to be released...",
+                    repo_dir.display()
+                ));
+                if shared {
+                    file.lock_shared()?;
+                } else {
+                    file.lock()?;
+                }
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        if is_current_lock(&file, repo_dir) {
+            return Ok(file);
+        }
+    }
+}
+
+/// Lock the cache entry at `repo_dir` if no other process holds it.
+pub fn try_lock_repo(repo_dir: &Path) -> Result<Option<File>> {
+    loop {
+        let file = open_lock_file(repo_dir)?;
+        match file.try_lock() {
+            Ok(()) if is_current_lock(&file, repo_dir) => return Ok(Some(file)),
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+    }
+}
+
+/// Whether `file` is still the lock file of `repo_dir`, rather than one
+/// removed along with its entry while this process waited for it.
+#[cfg(unix)]
+fn is_current_lock(file: &File, repo_dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (
+        file.metadata(),
+        std::fs::metadata(repo_dir.with_extension("lock")),
+    ) {
+        (Ok(held), Ok(on_disk)) => held.dev() == on_disk.dev() && held.ino() == on_disk.ino(),
+        _ => false,
+    }
+}
+
+/// Open files can't be removed on other platforms, so a held lock is current.
+#[cfg(not(unix))]
+fn is_current_lock(_file: &File, _repo_dir: &Path) -> bool {
+    true
+}
+
+fn open_lock_file(repo_dir: &Path) -> Result<File> {
+    if let Some(parent) = repo_dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(repo_dir.with_extension("lock"))?)
+}
+
+/// A repository opened for reading.
+///
+/// When it is a cache entry, a shared lock is held until this is dropped,
+/// so the entry is not fetched into, re-cloned or removed mid-read.
+struct SharedRepo {
+    repo: Repository,
+    _lock: Option<File>,
+}
+
+impl std::ops::Deref for SharedRepo {
+    type Target = Repository;
+
+    fn deref(&self) -> &Repository {
+        &self.repo
+    }
+}
+
+/// Open the repository at `repo_path` for reading.
+fn open_shared(repo_path: &Path) -> Result<SharedRepo> {
+    let in_cache = repo_path.exists() && cache_dir().is_ok_and(|c| repo_path.starts_with(c));
+    let lock = if in_cache {
+        Some(lock_entry(repo_path, true)?)
+    } else {
+        None
+    };
+    let repo = Repository::open(repo_path)?;
+    Ok(SharedRepo { repo, _lock: lock })
+}
+
+/// What a fetch needs from a remote.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FetchTarget {
+    /// Every branch and tag, with full history.
+    Full,
+    Tag(String),
+    Branch(String),
+    /// A full commit SHA.
+    Commit(String),
+}
+
+impl FetchTarget {
+    /// The target for a commit. Remotes only serve full SHAs, so an
+    /// abbreviated one needs a full fetch.
+    pub fn commit(rev: &str) -> Self {
+        if rev.len() == 40 && rev.bytes().all(|b| b.is_ascii_hexdigit()) {
+            Self::Commit(rev.to_string())
+        } else {
+            Self::Full
+        }
+    }
+
+    fn refspec(&self) -> Option<String> {
+        match self {
+            Self::Full => None,
+            Self::Tag(tag) => Some(format!("+refs/tags/{tag}:refs/tags/{tag}")),
+            Self::Branch(branch) => {
+                Some(format!("+refs/heads/{branch}:refs/remotes/origin/{branch}"))
+            }
+            // Keep a ref so the commit survives `git gc`
+            Self::Commit(sha) => Some(format!("+{sha}:refs/nuance/{sha}")),
+        }
+    }
+
+    /// Whether the repo already holds what this target asks for.
+    fn is_present(&self, repo: &Repository) -> bool {
+        match self {
+            Self::Full => false,
+            Self::Tag(tag) => repo.find_reference(&format!("refs/tags/{tag}")).is_ok(),
+            Self::Branch(branch) => repo
+                .find_reference(&format!("refs/remotes/origin/{branch}"))
+                .is_ok(),
+            Self::Commit(sha) => {
+                git2::Oid::from_str(sha).is_ok_and(|oid| repo.find_commit(oid).is_ok())
+            }
+        }
+    }
+
+    /// How a missing target is listed in an offline error.
+    fn describe(&self, url: &str) -> String {
+        match self {
+            Self::Full => url.to_string(),
+            Self::Tag(tag) => format!("{url} (tag {tag})"),
+            Self::Branch(branch) => format!("{url} (branch {branch})"),
+            Self::Commit(sha) => format!("{url} @ {}", &sha[..12]),
+        }
+    }
+
+    /// Whether the repo already holds this target and the remote cannot
+    /// have moved it since. Branches and full fetches always hit the network.
+    fn is_settled(&self, repo: &Repository) -> bool {
+        matches!(self, Self::Tag(_) | Self::Commit(_)) && self.is_present(repo)
+    }
 }
 
 /// Clone a repository into the cache, or fetch updates if it already exists.
 /// Returns the path to the cached repo.
+///
+/// This fetches every branch and tag; use [`fetch_refs`] when only some
+/// refs are needed.
 pub fn clone_or_fetch(url: &str) -> Result<PathBuf> {
-    let cache = cache_dir()?;
-    std::fs::create_dir_all(&cache)?;
+    fetch_refs(url, &[FetchTarget::Full])
+}
+
+/// Fetch every branch and tag of each repository in `urls`, once per URL
+/// and several at a time. Returns the cached repo for each URL.
+///
+/// In offline mode, every repository missing from the cache is reported at
+/// once.
+pub fn clone_or_fetch_all<'a, I>(urls: I) -> Result<HashMap<String, PathBuf>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut unique: Vec<&str> = Vec::new();
+    for url in urls {
+        if !unique.contains(&url) {
+            unique.push(url);
+        }
+    }
+    if !is_offline() {
+        for url in &unique {
+            progress::println(&format!("  Fetching {url}..."));
+        }
+    }
+
+    let results = jobs::map(&unique, |url| clone_or_fetch(url));
+    let mut repos = HashMap::new();
+    let mut missing = Vec::new();
+    for (url, result) in unique.into_iter().zip(results) {
+        match result {
+            Ok(path) => {
+                repos.insert(url.to_string(), path);
+            }
+            Err(NuanceError::Offline { missing: urls }) => missing.extend(urls),
+            Err(e) => return Err(e),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(NuanceError::Offline { missing });
+    }
+    Ok(repos)
+}
 
-    let repo_dir = cache.join(url_to_dirname(url));
+/// Fetch `targets` from `url` into the cache, cloning the repo if needed.
+/// Returns the path to the cached repo.
+///
+/// Tags, branches and commits are fetched on their own, shallowly where the
+/// remote supports it, falling back to a full fetch when that fails. In
+/// offline mode the cached repo is used as is, and a repo that was never
+/// cloned or lacks one of `targets` is an error.
+pub fn fetch_refs(url: &str, targets: &[FetchTarget]) -> Result<PathBuf> {
+    let repo_dir = cached_repo(url)?;
+
+    if is_offline() {
+        check_cached(url, &repo_dir, targets)?;
+        cache::touch(&repo_dir);
+        return Ok(repo_dir);
+    }
+
+    // Held until the clone or fetch is done, so parallel runs take turns
+    let _lock = lock_repo(&repo_dir)?;
+    sync_repo(url, &repo_dir, targets)?;
+    cache::touch(&repo_dir);
+    Ok(repo_dir)
+}
+
+/// Make sure the cached repo at `repo_dir` holds every one of `targets`,
+/// listing everything missing in a single [`NuanceError::Offline`].
+fn check_cached(url: &str, repo_dir: &Path, targets: &[FetchTarget]) -> Result<()> {
+    if !repo_dir.exists() {
+        return Err(NuanceError::Offline {
+            missing: vec![url.to_string()],
+        });
+    }
+    let repo = open_shared(repo_dir)?;
+    let missing: Vec<String> = targets
+        .iter()
+        .filter(|t| **t != FetchTarget::Full && !t.is_present(&repo))
+        .map(|t| t.describe(url))
+        .collect();
+    if !missing.is_empty() {
+        return Err(NuanceError::Offline { missing });
+    }
+    Ok(())
+}
+
+/// Fetch `targets` from `url` into the cached repo at `repo_dir`, cloning
+/// it if missing.
+///
+/// A cached repo that cannot be opened or fetched into (left half-cloned by
+/// an interrupted run, or with corrupt objects) is re-cloned with a
+/// warning. Network and authentication errors are returned as is.
+fn sync_repo(url: &str, repo_dir: &Path, targets: &[FetchTarget]) -> Result<()> {
+    if !repo_dir.exists() {
+        return fresh_clone(url, repo_dir, targets);
+    }
+
+    let repo = match usable_repo(repo_dir) {
+        Ok(repo) => repo,
+        Err(e) => return reclone(url, repo_dir, targets, &e),
+    };
+    match fetch_into(&repo, url, targets) {
+        Err(e) if is_corruption(&e) => {
+            drop(repo);
+            reclone(url, repo_dir, targets, &e)
+        }
+        other => other,
+    }
+}
+
+/// Replace an unusable cached repo with a fresh clone, with a warning.
+fn reclone(
+    url: &str,
+    repo_dir: &Path,
+    targets: &[FetchTarget],
+    reason: &NuanceError,
+) -> Result<()> {
+    progress::println(&format!(
+        "  warning: cached repo for {url} is unusable ({reason}); re-cloning..."
+    ));
+    fresh_clone(url, repo_dir, targets)
+}
+
+/// Open a cached repo, checking that it has an `origin` remote and that
+/// every ref points at an object it holds.
+fn usable_repo(repo_dir: &Path) -> Result<Repository> {
+    let repo = Repository::open(repo_dir)?;
+    repo.find_remote("origin")?;
+    for reference in repo.references()? {
+        reference?.peel(git2::ObjectType::Any)?;
+    }
+    Ok(repo)
+}
+
+/// Whether a failed fetch points at a broken cached repo (missing or
+/// corrupt objects) rather than at the network, the remote or a ref that
+/// doesn't exist.
+fn is_corruption(err: &NuanceError) -> bool {
+    let NuanceError::Git(err) = err else {
+        return false;
+    };
+    matches!(
+        err.class(),
+        git2::ErrorClass::Odb | git2::ErrorClass::Repository | git2::ErrorClass::Object
+    )
+}
+
+/// Clone `url` into a temporary bare repo next to `repo_dir` and move it
+/// into place only once the clone has succeeded, replacing whatever was
+/// there.
+fn fresh_clone(url: &str, repo_dir: &Path, targets: &[FetchTarget]) -> Result<()> {
+    let partial = repo_dir.with_extension("partial");
+    if partial.exists() {
+        // Left behind by an interrupted run
+        std::fs::remove_dir_all(&partial)?;
+    }
+
+    let cloned = Repository::init_bare(&partial)
+        .map_err(NuanceError::from)
+        .and_then(|repo| {
+            repo.remote("origin", url)?;
+            fetch_into(&repo, url, targets)
+        });
+    if let Err(e) = cloned {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err(e);
+    }
 
     if repo_dir.exists() {
-        // Fetch latest from the remote
-        let repo = Repository::open(&repo_dir)?;
-        let mut remote = repo.find_remote("origin")?;
-        let callbacks = RemoteCallbacks::new();
-        let mut fetch_opts = FetchOptions::new();
-        fetch_opts.remote_callbacks(callbacks);
-        remote.fetch(&[] as &[&str], Some(&mut fetch_opts), None)?;
-        Ok(repo_dir)
+        std::fs::remove_dir_all(repo_dir)?;
+    }
+    std::fs::rename(&partial, repo_dir)?;
+    Ok(())
+}
+
+/// Fetch `targets` into `repo`, reporting how much was received.
+///
+/// Targeted fetches are shallow when the repo is new or already shallow (a
+/// depth limit would otherwise cut off history the repo already has). If
+/// a targeted fetch fails or leaves something missing, everything is
+/// fetched instead.
+fn fetch_into(repo: &Repository, url: &str, targets: &[FetchTarget]) -> Result<()> {
+    let wanted: Vec<&FetchTarget> = targets.iter().filter(|t| !t.is_settled(repo)).collect();
+    if wanted.is_empty() && !targets.is_empty() {
+        return Ok(());
+    }
+
+    let mut received = 0;
+    let full = wanted.contains(&&FetchTarget::Full) || wanted.is_empty();
+    if !full {
+        let refspecs: Vec<String> = wanted.iter().filter_map(|t| t.refspec()).collect();
+        let shallow = repo.is_shallow() || repo.references()?.next().is_none();
+        let mut result = fetch_once(repo, url, &refspecs, shallow.then_some(1), &mut received);
+        if shallow && matches!(result, Err(NuanceError::Git(_))) {
+            // Not every transport can fetch shallowly
+            result = fetch_once(repo, url, &refspecs, None, &mut received);
+        }
+        match result {
+            Ok(()) if wanted.iter().all(|t| t.is_present(repo)) => {
+                report_received(url, received);
+                return Ok(());
+            }
+            Err(e @ NuanceError::Auth { .. }) => return Err(e),
+            // Fall back to fetching everything
+            _ => {}
+        }
+    }
+
+    let depth = repo.is_shallow().then_some(UNSHALLOW);
+    fetch_once(repo, url, &[], depth, &mut received)?;
+    set_remote_head(repo);
+    report_received(url, received);
+    Ok(())
+}
+
+/// The fetch depth that turns a shallow repo into a complete one.
+const UNSHALLOW: i32 = i32::MAX;
+
+/// Run one fetch from `origin`: the given refspecs (or all branches and
+/// tags when empty), limited to `depth` commits if given, showing its
+/// progress. Adds the bytes received to `received`.
+fn fetch_once(
+    repo: &Repository,
+    url: &str,
+    refspecs: &[String],
+    depth: Option<i32>,
+    received: &mut usize,
+) -> Result<()> {
+    let (mut callbacks, attempts) = auth::callbacks(url);
+    let transfer = Rc::new(progress::start(
+        &repo_name_from_url(url).unwrap_or_else(|| url.to_string()),
+    ));
+    let sideband = Rc::clone(&transfer);
+    callbacks.transfer_progress(move |stats| {
+        transfer.update(&stats);
+        true
+    });
+    callbacks.sideband_progress(move |data| {
+        sideband.message(data);
+        true
+    });
+    let mut fetch_opts = FetchOptions::new();
+    fetch_opts.remote_callbacks(callbacks);
+    if let Some(depth) = depth {
+        fetch_opts.depth(depth);
+    }
+
+    let mut remote = repo.find_remote("origin")?;
+    let result = if refspecs.is_empty() {
+        fetch_opts.download_tags(AutotagOption::All);
+        remote.fetch(&[] as &[&str], Some(&mut fetch_opts), None)
     } else {
-        // Fresh clone
-        let callbacks = RemoteCallbacks::new();
-        let mut fetch_opts = FetchOptions::new();
-        fetch_opts.remote_callbacks(callbacks);
+        fetch_opts.download_tags(AutotagOption::None);
+        remote.fetch(refspecs, Some(&mut fetch_opts), None)
+    };
+    *received += remote.stats().received_bytes();
+    result.map_err(|e| auth::explain(url, e, &attempts))
+}
 
-        RepoBuilder::new()
-            .fetch_options(fetch_opts)
-            .clone(url, &repo_dir)?;
+/// Record the remote's default branch as `refs/remotes/origin/HEAD`, as a
+/// clone would.
+fn set_remote_head(repo: &Repository) {
+    let Ok(remote) = repo.find_remote("origin") else {
+        return;
+    };
+    if let Ok(head) = remote.default_branch()
+        && let Some(branch) = head.as_str().and_then(|h| h.strip_prefix("refs/heads/"))
+    {
+        let _ = repo.reference_symbolic(
+            "refs/remotes/origin/HEAD",
+            &format!("refs/remotes/origin/{branch}"),
+            true,
+            "nuance: remote HEAD",
+        );
+    }
+}
 
-        Ok(repo_dir)
+fn report_received(url: &str, bytes: usize) {
+    if bytes > 0 {
+        progress::println(&format!(
+            "  Received {} from {url}",
+            cache::format_size(bytes as u64)
+        ));
     }
 }
 
+/// Return the cached repo for `url`, fetching only if it doesn't already
+/// contain commit `rev`.
+pub fn ensure_commit(url: &str, rev: &str) -> Result<PathBuf> {
+    let repo_dir = cached_repo(url)?;
+    if repo_dir.exists() && has_commit(&repo_dir, rev) {
+        cache::touch(&repo_dir);
+        return Ok(repo_dir);
+    }
+    if is_offline() {
+        return Err(NuanceError::Offline {
+            missing: vec![format!("{url} @ {rev}")],
+        });
+    }
+    fetch_refs(url, &[FetchTarget::commit(rev)])
+}
+
+/// Whether the repository at `repo_path` contains commit `sha`.
+pub fn has_commit(repo_path: &Path, sha: &str) -> bool {
+    let Ok(repo) = open_shared(repo_path) else {
+        return false;
+    };
+    git2::Oid::from_str(sha).is_ok_and(|oid| repo.find_commit(oid).is_ok())
+}
+
 /// Resolve a ref spec (tag, branch name, or commit SHA) to a full commit SHA string.
 pub fn resolve_ref(repo_path: &Path, spec: &str, kind: RefKind) -> Result<String> {
-    let repo = Repository::open(repo_path)?;
+    let repo = open_shared(repo_path)?;
 
     match kind {
         RefKind::Rev => {
@@ -79,13 +624,32 @@ pub fn resolve_ref(repo_path: &Path, spec: &str, kind: RefKind) -> Result<String
     }
 }
 
+/// Resolve a full or abbreviated commit SHA to the full SHA.
+pub fn resolve_commit(repo_path: &Path, spec: &str) -> Result<String> {
+    let repo = open_shared(repo_path)?;
+    let commit = repo
+        .revparse_single(spec)
+        .and_then(|obj| obj.peel_to_commit())
+        .map_err(|_| NuanceError::Other(format!("commit '{spec}' not found")))?;
+    Ok(commit.id().to_string())
+}
+
+/// Count the commits reachable from `to` but not from `from`.
+pub fn count_commits_between(repo_path: &Path, from: &str, to: &str) -> Result<usize> {
+    let repo = open_shared(repo_path)?;
+    let mut walk = repo.revwalk()?;
+    walk.push(git2::Oid::from_str(to)?)?;
+    walk.hide(git2::Oid::from_str(from)?)?;
+    Ok(walk.count())
+}
+
 /// Checkout a specific commit and export the working tree (without .git/) to `dest`.
-pub fn export_to(repo_path: &Path, sha: &str, dest: &Path) -> Result<()> {
-    let repo = Repository::open(repo_path)?;
-    let oid = git2::Oid::from_str(sha)
-        .map_err(|_| NuanceError::Other(format!("invalid commit SHA: {sha}")))?;
-    let commit = repo.find_commit(oid)?;
-    let tree = commit.tree()?;
+///
+/// When `subdir` is given, only that subtree is exported, so its contents
+/// end up directly under `dest`.
+pub fn export_to(repo_path: &Path, sha: &str, subdir: Option<&str>, dest: &Path) -> Result<()> {
+    let repo = open_shared(repo_path)?;
+    let tree = commit_tree(&repo, sha, subdir)?;
 
     // Clean destination
     if dest.exists() {
@@ -122,6 +686,74 @@ pub fn export_to(repo_path: &Path, sha: &str, dest: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Compute the checksum of a commit's tree (or `subdir` of it) without
+/// exporting it. Produces the same digest as hashing the exported directory.
+pub fn checksum_at(repo_path: &Path, sha: &str, subdir: Option<&str>) -> Result<String> {
+    let repo = open_shared(repo_path)?;
+    let tree = commit_tree(&repo, sha, subdir)?;
+
+    let mut files = Vec::new();
+    tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob)
+            && let Some(name) = entry.name()
+            && let Ok(blob) = repo.find_blob(entry.id())
+        {
+            let mut rel = PathBuf::new();
+            for part in dir.split('/').filter(|p| !p.is_empty()) {
+                rel.push(part);
+            }
+            rel.push(name);
+            files.push((rel, blob.content().to_vec()));
+        }
+        git2::TreeWalkResult::Ok
+    })?;
+
+    Ok(checksum::hash_files(files))
+}
+
+/// Read a single file from a commit (or `subdir` of it) without exporting
+/// the tree. Returns `None` if the file doesn't exist at that commit.
+pub fn read_file_at(
+    repo_path: &Path,
+    sha: &str,
+    subdir: Option<&str>,
+    file: &str,
+) -> Result<Option<String>> {
+    let repo = open_shared(repo_path)?;
+    let tree = commit_tree(&repo, sha, subdir)?;
+    let Some(entry) = tree.get_name(file) else {
+        return Ok(None);
+    };
+    let Ok(blob) = entry.to_object(&repo).and_then(|obj| obj.peel_to_blob()) else {
+        return Ok(None);
+    };
+    String::from_utf8(blob.content().to_vec())
+        .map(Some)
+        .map_err(|_| NuanceError::Other(format!("{file} at commit {sha} is not valid UTF-8")))
+}
+
+/// Look up the tree of a commit, or of `subdir` within it.
+fn commit_tree<'r>(
+    repo: &'r Repository,
+    sha: &str,
+    subdir: Option<&str>,
+) -> Result<git2::Tree<'r>> {
+    let oid = git2::Oid::from_str(sha)
+        .map_err(|_| NuanceError::Other(format!("invalid commit SHA: {sha}")))?;
+    let commit = repo.find_commit(oid)?;
+    match subdir {
+        Some(subdir) => commit
+            .tree()?
+            .get_path(Path::new(subdir))
+            .and_then(|entry| entry.to_object(repo))
+            .and_then(|obj| obj.peel_to_tree())
+            .map_err(|_| {
+                NuanceError::Other(format!("subdirectory '{subdir}' not found at commit {sha}"))
+            }),
+        None => Ok(commit.tree()?),
+    }
+}
+
 /// The kind of git ref being resolved.
 #[derive(Debug, Clone, Copy)]
 pub enum RefKind {
@@ -130,43 +762,59 @@ pub enum RefKind {
     Branch,
 }
 
-impl RefKind {
-    /// Determine the ref kind from a dependency spec.
-    pub fn from_spec(tag: &Option<String>, rev: &Option<String>, _branch: &Option<String>) -> Self {
-        if rev.is_some() {
-            RefKind::Rev
-        } else if tag.is_some() {
-            RefKind::Tag
-        } else {
-            RefKind::Branch
+/// Find the latest tag in a cached repository.
+///
+/// Tags are compared as semver versions (with an optional `v` prefix), and
+/// pre-releases are skipped unless `include_pre` is set. Only when no tag
+/// parses as a version at all is the most recently dated tag chosen.
+/// If no suitable tag exists, returns `None`.
+pub fn latest_tag(repo_path: &Path, include_pre: bool) -> Result<Option<String>> {
+    let tags = list_tags(repo_path)?;
+
+    if tags.iter().any(|t| version::parse_tag(t).is_some()) {
+        return Ok(version::highest(&tags, include_pre).map(str::to_string));
+    }
+
+    // No version tags: fall back to the newest tag by date
+    let repo = open_shared(repo_path)?;
+    let mut newest: Option<(i64, String)> = None;
+    for tag in tags {
+        let Some(time) = tag_time(&repo, &tag) else {
+            continue;
+        };
+        if newest.as_ref().is_none_or(|(t, _)| time > *t) {
+            newest = Some((time, tag));
         }
     }
+    Ok(newest.map(|(_, tag)| tag))
 }
 
-/// Find the latest tag in a cached repository.
-///
-/// Looks for tags matching common semver patterns (v1.2.3, 1.2.3, etc.)
-/// and returns the most recent one. If no tags exist, returns `None`.
-pub fn latest_tag(repo_path: &Path) -> Result<Option<String>> {
-    let repo = Repository::open(repo_path)?;
+/// The date of a tag: the tagger time for annotated tags, otherwise the
+/// time of the tagged commit.
+fn tag_time(repo: &Repository, tag: &str) -> Option<i64> {
+    let obj = repo.revparse_single(&format!("refs/tags/{tag}")).ok()?;
+    if let Some(tagger) = obj.as_tag().and_then(|t| t.tagger()) {
+        return Some(tagger.when().seconds());
+    }
+    let commit = obj.peel_to_commit().ok()?;
+    Some(commit.time().seconds())
+}
+
+/// List all tag names in a cached repository.
+pub fn list_tags(repo_path: &Path) -> Result<Vec<String>> {
+    let repo = open_shared(repo_path)?;
     let mut tags: Vec<String> = Vec::new();
 
     repo.tag_foreach(|_oid, name| {
-        if let Ok(name_str) = std::str::from_utf8(name) {
-            if let Some(tag_name) = name_str.strip_prefix("refs/tags/") {
-                tags.push(tag_name.to_string());
-            }
+        if let Ok(name_str) = std::str::from_utf8(name)
+            && let Some(tag_name) = name_str.strip_prefix("refs/tags/")
+        {
+            tags.push(tag_name.to_string());
         }
         true // continue iterating
     })?;
 
-    if tags.is_empty() {
-        return Ok(None);
-    }
-
-    // Sort tags — simple lexicographic on the numeric parts works for semver
-    tags.sort();
-    Ok(tags.last().cloned())
+    Ok(tags)
 }
 
 /// Extract a package name from a git URL.
@@ -178,9 +826,23 @@ pub fn repo_name_from_url(url: &str) -> Option<String> {
     trimmed.rsplit('/').next().map(|s| s.to_string())
 }
 
-/// Detect the default branch of a cached repository (main, master, etc.)
+/// Detect the default branch of a cached repository.
+///
+/// Reads the `origin/HEAD` ref recorded at clone time, asks the remote for
+/// its HEAD only when that is missing, and finally guesses `main`/`master`.
 pub fn default_branch(repo_path: &Path) -> Result<String> {
-    let repo = Repository::open(repo_path)?;
+    let repo = open_shared(repo_path)?;
+
+    if let Ok(reference) = repo.find_reference("refs/remotes/origin/HEAD")
+        && let Some(target) = reference.symbolic_target()
+        && let Some(branch) = target.strip_prefix("refs/remotes/origin/")
+    {
+        return Ok(branch.to_string());
+    }
+
+    if let Some(branch) = remote_head_branch(&repo) {
+        return Ok(branch);
+    }
 
     // Try common branch names
     for branch in &["main", "master"] {
@@ -194,3 +856,381 @@ pub fn default_branch(repo_path: &Path) -> Result<String> {
         "could not determine default branch".to_string(),
     ))
 }
+
+/// Connect to `origin` and read which branch its HEAD points at.
+fn remote_head_branch(repo: &Repository) -> Option<String> {
+    if is_offline() {
+        return None;
+    }
+    let mut remote = repo.find_remote("origin").ok()?;
+    let (callbacks, _) = auth::callbacks(remote.url()?);
+    remote
+        .connect_auth(git2::Direction::Fetch, Some(callbacks), None)
+        .ok()?;
+    let head = remote.default_branch().ok()?;
+    let _ = remote.disconnect();
+    head.as_str()?
+        .strip_prefix("refs/heads/")
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, commit_at, tag, temp_dir};
+
+    #[test]
+    fn cache_keys_are_normalized_and_collision_free() {
+        let key = cache_key("https://github.com/user/nu-utils");
+        assert!(key.starts_with("user_nu-utils-"), "{key}");
+        assert_eq!(key, cache_key("https://GitHub.com/user/nu-utils.git"));
+        assert_eq!(key, cache_key(" https://github.com/user/nu-utils/ "));
+        assert_ne!(key, cache_key("https://github.com/User/nu-utils"));
+
+        assert_ne!(cache_key("https://a.b/c"), cache_key("https://a_b/c"));
+        assert_eq!(
+            normalize_url("git@GitHub.com:User/repo.git"),
+            "git@github.com:User/repo"
+        );
+        assert_eq!(normalize_url("file:///tmp/Repo/"), "file:///tmp/Repo");
+    }
+
+    #[test]
+    fn cache_entry_lock_is_exclusive() {
+        let dir = temp_dir("lock");
+        let repo_dir = dir.join("user_repo-0123456789abcdef");
+
+        let held = lock_repo(&repo_dir).unwrap();
+        assert!(try_lock_repo(&repo_dir).unwrap().is_none());
+        drop(held);
+        assert!(try_lock_repo(&repo_dir).unwrap().is_some());
+
+        // Readers share the entry but keep writers out
+        let first = lock_entry(&repo_dir, true).unwrap();
+        let second = lock_entry(&repo_dir, true).unwrap();
+        assert!(try_lock_repo(&repo_dir).unwrap().is_none());
+        drop((first, second));
+        assert!(try_lock_repo(&repo_dir).unwrap().is_some());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recovers_unusable_cached_repo() {
+        let dir = temp_dir("recover");
+        let source = dir.join("source");
+        std::fs::create_dir_all(&source).unwrap();
+        let sha = commit(
+            &Repository::init(&source).unwrap(),
+            &[("mod.nu", "export def hi [] {}")],
+        )
+        .to_string();
+        let url = format!("file://{}", source.display());
+
+        // Half-cloned: a repo with no remote and no commits, plus leftovers
+        // from an interrupted clone
+        let repo_dir = dir.join("cache").join("source-0123456789abcdef");
+        Repository::init(&repo_dir).unwrap();
+        std::fs::create_dir_all(repo_dir.with_extension("partial")).unwrap();
+
+        sync_repo(&url, &repo_dir, &[FetchTarget::Full]).unwrap();
+        assert!(has_commit(&repo_dir, &sha));
+        assert!(!repo_dir.with_extension("partial").exists());
+
+        // A healthy cached repo is fetched into, not re-cloned
+        std::fs::write(repo_dir.join("marker"), "").unwrap();
+        sync_repo(&url, &repo_dir, &[FetchTarget::Full]).unwrap();
+        assert!(repo_dir.join("marker").exists());
+
+        // So is one asked for a tag the remote doesn't have
+        let _ = sync_repo(&url, &repo_dir, &[FetchTarget::Tag("v9.9.9".to_string())]);
+        assert!(repo_dir.join("marker").exists());
+
+        let missing_ref = git2::Error::new(
+            git2::ErrorCode::NotFound,
+            git2::ErrorClass::Reference,
+            "no such tag",
+        );
+        assert!(!is_corruption(&NuanceError::Git(missing_ref)));
+        let missing_object = git2::Error::new(
+            git2::ErrorCode::NotFound,
+            git2::ErrorClass::Odb,
+            "object not found",
+        );
+        assert!(is_corruption(&NuanceError::Git(missing_object)));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_clone_leaves_no_partial_repo() {
+        let dir = temp_dir("failed_clone");
+        let repo_dir = dir.join("missing-0123456789abcdef");
+        let url = format!("file://{}", dir.join("does-not-exist").display());
+
+        assert!(sync_repo(&url, &repo_dir, &[FetchTarget::Full]).is_err());
+        assert!(!repo_dir.exists());
+        assert!(!repo_dir.with_extension("partial").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn export_subdir_only() {
+        let dir = temp_dir("export_subdir");
+        let repo_dir = dir.join("repo");
+        let sha = commit(
+            &Repository::init(&repo_dir).unwrap(),
+            &[
+                ("README.md", "root"),
+                ("modules/foo/mod.nu", "export def foo [] {}"),
+                ("modules/bar/mod.nu", "export def bar [] {}"),
+            ],
+        )
+        .to_string();
+
+        let dest = dir.join("out");
+        export_to(&repo_dir, &sha, Some("modules/foo"), &dest).unwrap();
+        assert!(dest.join("mod.nu").exists());
+        assert!(!dest.join("README.md").exists());
+        assert!(!dest.join("modules").exists());
+
+        let err = export_to(&repo_dir, &sha, Some("modules/missing"), &dest).unwrap_err();
+        assert!(err.to_string().contains("not found"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn reads_single_file_from_commit() {
+        let dir = temp_dir("read_file");
+        let repo_dir = dir.join("repo");
+        let sha = commit(
+            &Repository::init(&repo_dir).unwrap(),
+            &[
+                ("mod.toml", "root manifest"),
+                ("modules/foo/mod.toml", "foo manifest"),
+            ],
+        )
+        .to_string();
+
+        let root = read_file_at(&repo_dir, &sha, None, "mod.toml").unwrap();
+        assert_eq!(root.as_deref(), Some("root manifest"));
+        let foo = read_file_at(&repo_dir, &sha, Some("modules/foo"), "mod.toml").unwrap();
+        assert_eq!(foo.as_deref(), Some("foo manifest"));
+        assert_eq!(
+            read_file_at(&repo_dir, &sha, None, "missing.toml").unwrap(),
+            None
+        );
+        // A directory is not a file
+        assert_eq!(
+            read_file_at(&repo_dir, &sha, None, "modules").unwrap(),
+            None
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn checksum_matches_exported_directory() {
+        let dir = temp_dir("checksum_at");
+        let repo_dir = dir.join("repo");
+        let sha = commit(
+            &Repository::init(&repo_dir).unwrap(),
+            &[
+                ("mod.nu", "export use sub"),
+                ("sub/mod.nu", "export def hi [] {}"),
+                ("sub/deep/x.nu", "x"),
+            ],
+        )
+        .to_string();
+
+        let dest = dir.join("out");
+        export_to(&repo_dir, &sha, None, &dest).unwrap();
+        assert_eq!(
+            checksum_at(&repo_dir, &sha, None).unwrap(),
+            checksum::hash_directory(&dest).unwrap()
+        );
+
+        export_to(&repo_dir, &sha, Some("sub"), &dest).unwrap();
+        assert_eq!(
+            checksum_at(&repo_dir, &sha, Some("sub")).unwrap(),
+            checksum::hash_directory(&dest).unwrap()
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn fetches_only_requested_refs() {
+        let dir = temp_dir("targeted");
+        let origin_dir = dir.join("origin");
+        let origin = Repository::init(&origin_dir).unwrap();
+        let first = commit_at(&origin, &[("a.nu", "1")], 1_000);
+        tag(&origin, "v1.0.0", first);
+        let second = commit_at(&origin, &[("a.nu", "2")], 2_000);
+        tag(&origin, "v2.0.0", second);
+        let url = format!("file://{}", origin_dir.display());
+        let repo_dir = dir.join("cache").join("origin-0123456789abcdef");
+
+        sync_repo(&url, &repo_dir, &[FetchTarget::Tag("v1.0.0".to_string())]).unwrap();
+        assert_eq!(list_tags(&repo_dir).unwrap(), vec!["v1.0.0".to_string()]);
+
+        // A commit on no branch or tag the cache has seen
+        let third = commit_at(&origin, &[("a.nu", "3")], 3_000);
+        let target = FetchTarget::commit(&third.to_string());
+        assert_eq!(target, FetchTarget::Commit(third.to_string()));
+        sync_repo(&url, &repo_dir, &[target]).unwrap();
+        assert!(has_commit(&repo_dir, &third.to_string()));
+        assert_eq!(list_tags(&repo_dir).unwrap().len(), 1);
+
+        // Abbreviated commits and version ranges need everything
+        assert_eq!(FetchTarget::commit("abc1234"), FetchTarget::Full);
+        sync_repo(&url, &repo_dir, &[FetchTarget::Full]).unwrap();
+        assert_eq!(list_tags(&repo_dir).unwrap().len(), 2);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn offline_lists_repos_missing_from_the_cache() {
+        let dir = temp_dir("offline_repo");
+        let repo_dir = dir.join("cache").join("origin-0123456789abcdef");
+        let err =
+            check_cached("https://example.com/dep", &repo_dir, &[FetchTarget::Full]).unwrap_err();
+        assert!(matches!(
+            &err,
+            NuanceError::Offline { missing } if missing == &["https://example.com/dep"]
+        ));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn offline_lists_refs_missing_from_a_cached_repo() {
+        let dir = temp_dir("offline_refs");
+        let origin_dir = dir.join("origin");
+        let origin = Repository::init(&origin_dir).unwrap();
+        let first = commit_at(&origin, &[("a.nu", "1")], 1_000);
+        tag(&origin, "v1.0.0", first);
+        let url = format!("file://{}", origin_dir.display());
+        let repo_dir = dir.join("cache").join("origin-0123456789abcdef");
+        sync_repo(&url, &repo_dir, &[FetchTarget::Tag("v1.0.0".to_string())]).unwrap();
+
+        let present = [FetchTarget::Full, FetchTarget::Tag("v1.0.0".to_string())];
+        check_cached(&url, &repo_dir, &present).unwrap();
+
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        let wanted = [
+            FetchTarget::Tag("v1.0.0".to_string()),
+            FetchTarget::Tag("v2.0.0".to_string()),
+            FetchTarget::Branch("next".to_string()),
+            FetchTarget::commit(sha),
+        ];
+        let err = check_cached(&url, &repo_dir, &wanted).unwrap_err();
+        let NuanceError::Offline { missing } = err else {
+            panic!("expected an offline error, got {err}");
+        };
+        assert_eq!(
+            missing,
+            vec![
+                format!("{url} (tag v2.0.0)"),
+                format!("{url} (branch next)"),
+                format!("{url} @ 0123456789ab"),
+            ]
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn refetches_branches_that_moved() {
+        let dir = temp_dir("moved_branch");
+        let origin_dir = dir.join("origin");
+        let origin = Repository::init(&origin_dir).unwrap();
+        commit_at(&origin, &[("a.nu", "1")], 1_000);
+        let branch = origin.head().unwrap().shorthand().unwrap().to_string();
+        let url = format!("file://{}", origin_dir.display());
+        let repo_dir = dir.join("cache").join("origin-0123456789abcdef");
+        let target = [FetchTarget::Branch(branch.clone())];
+
+        sync_repo(&url, &repo_dir, &target).unwrap();
+        let tip = commit_at(&origin, &[("a.nu", "2")], 2_000);
+        sync_repo(&url, &repo_dir, &target).unwrap();
+
+        assert_eq!(
+            resolve_ref(&repo_dir, &branch, RefKind::Branch).unwrap(),
+            tip.to_string()
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn latest_tag_uses_semver_order() {
+        let dir = temp_dir("latest_semver");
+        let repo = Repository::init(&dir).unwrap();
+        let c1 = commit_at(&repo, &[("a.nu", "1")], 1_000);
+        let c2 = commit_at(&repo, &[("a.nu", "2")], 2_000);
+        tag(&repo, "v1.9.0", c1);
+        tag(&repo, "v1.10.0", c1);
+        tag(&repo, "v2.0.0-rc1", c2);
+        tag(&repo, "nightly", c2);
+
+        assert_eq!(latest_tag(&dir, false).unwrap().as_deref(), Some("v1.10.0"));
+        assert_eq!(
+            latest_tag(&dir, true).unwrap().as_deref(),
+            Some("v2.0.0-rc1")
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn latest_tag_falls_back_to_newest_non_semver_tag() {
+        let dir = temp_dir("latest_dated");
+        let repo = Repository::init(&dir).unwrap();
+        let old = commit_at(&repo, &[("a.nu", "1")], 1_000);
+        let new = commit_at(&repo, &[("a.nu", "2")], 2_000);
+        tag(&repo, "zzz-old", old);
+        tag(&repo, "aaa-new", new);
+
+        assert_eq!(latest_tag(&dir, false).unwrap().as_deref(), Some("aaa-new"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn default_branch_follows_remote_head() {
+        let dir = temp_dir("default_branch");
+        let origin_dir = dir.join("origin");
+        let origin = Repository::init(&origin_dir).unwrap();
+        commit_at(&origin, &[("a.nu", "1")], 1_000);
+        let head = origin.head().unwrap().peel_to_commit().unwrap();
+        origin.branch("trunk", &head, false).unwrap();
+        origin.set_head("refs/heads/trunk").unwrap();
+
+        let clone_dir = dir.join("clone");
+        git2::build::RepoBuilder::new()
+            .clone(origin_dir.to_str().unwrap(), &clone_dir)
+            .unwrap();
+
+        assert_eq!(default_branch(&clone_dir).unwrap(), "trunk");
+
+        // The recorded origin/HEAD wins without asking the remote
+        origin.branch("next", &head, false).unwrap();
+        origin.set_head("refs/heads/next").unwrap();
+        assert_eq!(default_branch(&clone_dir).unwrap(), "trunk");
+
+        // Only a missing origin/HEAD falls back to the remote
+        Repository::open(&clone_dir)
+            .unwrap()
+            .find_reference("refs/remotes/origin/HEAD")
+            .unwrap()
+            .delete()
+            .unwrap();
+        assert_eq!(default_branch(&clone_dir).unwrap(), "next");
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
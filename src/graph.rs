@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::error::{NuanceError, Result};
+use crate::lockfile::Lockfile;
+use crate::resolver::{self, ResolvedDep};
+
+/// A package in the dependency graph.
+#[derive(Debug, Clone, Serialize)]
+pub struct Node {
+    /// The resolved tag, abbreviated commit or path.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub version: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dev: bool,
+    /// Outgoing edges: dependencies, or dependents once inverted.
+    pub dependencies: Vec<String>,
+}
+
+/// The resolved dependency graph of a project, rooted at the project itself.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    /// Where traversal starts: the project, or leaf packages once inverted.
+    starts: Vec<String>,
+    nodes: BTreeMap<String, Node>,
+}
+
+/// Output formats for `nuance tree`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    Text,
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl Graph {
+    /// Build the graph from the root's direct dependencies and the resolved packages.
+    pub fn new(root: &str, root_deps: &[String], resolved: &[ResolvedDep]) -> Self {
+        let mut nodes = BTreeMap::new();
+        let mut direct = root_deps.to_vec();
+        direct.sort();
+        nodes.insert(
+            root.to_string(),
+            Node {
+                version: String::new(),
+                dev: false,
+                dependencies: direct,
+            },
+        );
+        for dep in resolved {
+            let version = match (&dep.tag, &dep.rev, &dep.path) {
+                (Some(tag), _, _) => tag.clone(),
+                (None, Some(rev), _) => rev[..7.min(rev.len())].to_string(),
+                (None, None, Some(path)) => path.clone(),
+                (None, None, None) => String::new(),
+            };
+            nodes.insert(
+                dep.name.clone(),
+                Node {
+                    version,
+                    dev: dep.dev,
+                    dependencies: dep.dependencies.clone(),
+                },
+            );
+        }
+        Self {
+            starts: vec![root.to_string()],
+            nodes,
+        }
+    }
+
+    /// Build the graph recorded in a lockfile, without resolving anything.
+    ///
+    /// Returns `None` for version 1 lockfiles, which have no dependency edges.
+    pub fn from_lockfile(root: &str, lockfile: &Lockfile) -> Option<Self> {
+        if !lockfile.has_edges() {
+            return None;
+        }
+        let resolved = resolver::resolve_from_lock(&lockfile.packages);
+        Some(Self::new(root, &lockfile.roots, &resolved))
+    }
+
+    /// Reverse every edge, so each package points at the packages that
+    /// depend on it. Traversal starts from packages with no dependencies.
+    pub fn inverted(&self) -> Self {
+        let mut nodes: BTreeMap<String, Node> = self
+            .nodes
+            .iter()
+            .map(|(name, node)| {
+                let node = Node {
+                    dependencies: Vec::new(),
+                    ..node.clone()
+                };
+                (name.clone(), node)
+            })
+            .collect();
+        for (parent, node) in &self.nodes {
+            for child in &node.dependencies {
+                if let Some(child_node) = nodes.get_mut(child) {
+                    child_node.dependencies.push(parent.clone());
+                }
+            }
+        }
+        let starts = self
+            .nodes
+            .iter()
+            .filter(|(name, node)| node.dependencies.is_empty() && !self.starts.contains(name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        Self { starts, nodes }
+    }
+
+    /// Start traversal at a single package.
+    pub fn focus(mut self, name: &str) -> Result<Self> {
+        if !self.nodes.contains_key(name) {
+            return Err(NuanceError::Other(format!(
+                "package '{name}' is not in the dependency graph"
+            )));
+        }
+        self.starts = vec![name.to_string()];
+        Ok(self)
+    }
+
+    /// Render the graph, descending at most `depth` levels from the start.
+    pub fn render(&self, format: Format, depth: Option<usize>) -> Result<String> {
+        match format {
+            Format::Text => Ok(self.render_text(depth)),
+            Format::Dot => Ok(self.render_dot(depth)),
+            Format::Mermaid => Ok(self.render_mermaid(depth)),
+            Format::Json => self.render_json(depth),
+        }
+    }
+
+    /// Every path from the root to `name`, each as a list of package names.
+    pub fn paths_to(&self, name: &str) -> Result<Vec<Vec<String>>> {
+        if !self.nodes.contains_key(name) || self.starts.iter().any(|s| s == name) {
+            return Err(NuanceError::Other(format!(
+                "package '{name}' is not a dependency"
+            )));
+        }
+        let mut paths = Vec::new();
+        for start in &self.starts {
+            let mut path = vec![start.clone()];
+            self.collect_paths(&mut path, name, &mut paths);
+        }
+        Ok(paths)
+    }
+
+    fn collect_paths(&self, path: &mut Vec<String>, target: &str, paths: &mut Vec<Vec<String>>) {
+        let current = path.last().expect("path is never empty");
+        if current == target {
+            paths.push(path.clone());
+            return;
+        }
+        for child in self.children(current) {
+            // Guard against cycles
+            if path.contains(child) {
+                continue;
+            }
+            path.push(child.clone());
+            self.collect_paths(path, target, paths);
+            path.pop();
+        }
+    }
+
+    fn children(&self, name: &str) -> &[String] {
+        self.nodes
+            .get(name)
+            .map(|n| n.dependencies.as_slice())
+            .unwrap_or_default()
+    }
+
+    fn label(&self, name: &str) -> String {
+        match self.nodes.get(name) {
+            Some(node) if !node.version.is_empty() => format!("{name} {}", node.version),
+            _ => name.to_string(),
+        }
+    }
+
+    /// Edges within `depth` levels of the start, in a stable order.
+    fn visible_edges(&self, depth: Option<usize>) -> (BTreeSet<String>, Vec<(String, String)>) {
+        let mut seen: BTreeSet<String> = self.starts.iter().cloned().collect();
+        let mut edges = BTreeSet::new();
+        let mut frontier = self.starts.clone();
+        let mut level = 0;
+        while !frontier.is_empty() && depth.is_none_or(|d| level < d) {
+            let mut next = Vec::new();
+            for name in &frontier {
+                for child in self.children(name) {
+                    edges.insert((name.clone(), child.clone()));
+                    if seen.insert(child.clone()) {
+                        next.push(child.clone());
+                    }
+                }
+            }
+            frontier = next;
+            level += 1;
+        }
+        (seen, edges.into_iter().collect())
+    }
+
+    fn render_text(&self, depth: Option<usize>) -> String {
+        let mut out = String::new();
+        let mut expanded = BTreeSet::new();
+        for start in &self.starts {
+            out.push_str(&self.label(start));
+            out.push('\n');
+            self.render_children(start, "", 1, depth, &mut expanded, &mut out);
+        }
+        out
+    }
+
+    fn render_children(
+        &self,
+        name: &str,
+        prefix: &str,
+        level: usize,
+        depth: Option<usize>,
+        expanded: &mut BTreeSet<String>,
+        out: &mut String,
+    ) {
+        if depth.is_some_and(|d| level > d) {
+            return;
+        }
+        let children = self.children(name);
+        for (i, child) in children.iter().enumerate() {
+            let last = i + 1 == children.len();
+            let branch = if last { "└── " } else { "├── " };
+            let mut line = format!("{prefix}{branch}{}", self.label(child));
+            if self.nodes.get(child).is_some_and(|n| n.dev) {
+                line.push_str(" (dev)");
+            }
+            // Print shared subtrees once; later occurrences are marked with (*)
+            let repeated = !self.children(child).is_empty() && !expanded.insert(child.clone());
+            if repeated {
+                line.push_str(" (*)");
+            }
+            out.push_str(&line);
+            out.push('\n');
+            if !repeated {
+                let child_prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+                self.render_children(child, &child_prefix, level + 1, depth, expanded, out);
+            }
+        }
+    }
+
+    fn render_dot(&self, depth: Option<usize>) -> String {
+        let (nodes, edges) = self.visible_edges(depth);
+        let mut out = String::from("digraph dependencies {\n");
+        for name in &nodes {
+            let _ = writeln!(out, "    \"{name}\" [label=\"{}\"];", self.label(name));
+        }
+        for (from, to) in &edges {
+            let _ = writeln!(out, "    \"{from}\" -> \"{to}\";");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn render_mermaid(&self, depth: Option<usize>) -> String {
+        let (nodes, edges) = self.visible_edges(depth);
+        // Package names may contain characters Mermaid ids don't allow
+        let ids: BTreeMap<&String, String> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name, format!("n{i}")))
+            .collect();
+        let mut out = String::from("graph TD\n");
+        for name in &nodes {
+            let _ = writeln!(out, "    {}[\"{}\"]", ids[name], self.label(name));
+        }
+        for (from, to) in &edges {
+            let _ = writeln!(out, "    {} --> {}", ids[from], ids[to]);
+        }
+        out
+    }
+
+    fn render_json(&self, depth: Option<usize>) -> Result<String> {
+        #[derive(Serialize)]
+        struct JsonGraph<'a> {
+            roots: &'a [String],
+            packages: BTreeMap<&'a String, Node>,
+        }
+
+        let (nodes, edges) = self.visible_edges(depth);
+        let packages = nodes
+            .iter()
+            .map(|name| {
+                let node = Node {
+                    dependencies: edges
+                        .iter()
+                        .filter(|(from, _)| from == name)
+                        .map(|(_, to)| to.clone())
+                        .collect(),
+                    ..self.nodes[name].clone()
+                };
+                (name, node)
+            })
+            .collect();
+        let graph = JsonGraph {
+            roots: &self.starts,
+            packages,
+        };
+        serde_json::to_string_pretty(&graph)
+            .map(|json| json + "\n")
+            .map_err(|e| NuanceError::Other(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dep(name: &str, tag: &str, dependencies: &[&str]) -> ResolvedDep {
+        ResolvedDep {
+            name: name.to_string(),
+            git: Some(format!("https://example.com/{name}")),
+            path: None,
+            subdir: None,
+            tag: Some(tag.to_string()),
+            rev: Some("0123456789abcdef".to_string()),
+            dev: false,
+            requested: None,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    fn sample() -> Graph {
+        let resolved = vec![
+            dep("nu-a", "v1.0.0", &["nu-c"]),
+            dep("nu-b", "v2.0.0", &["nu-c"]),
+            dep("nu-c", "v0.3.0", &[]),
+        ];
+        Graph::new("app", &["nu-b".to_string(), "nu-a".to_string()], &resolved)
+    }
+
+    #[test]
+    fn builds_graph_from_lockfile_edges() {
+        let lockfile = Lockfile::from_str(
+            r#"version = 2
+roots = ["nu-a"]
+
+[[package]]
+name = "nu-a"
+git = "https://example.com/nu-a"
+tag = "v1.0.0"
+rev = "0123456789abcdef"
+sha256 = "abc"
+dependencies = ["nu-c"]
+
+[[package]]
+name = "nu-c"
+git = "https://example.com/nu-c"
+tag = "v0.3.0"
+rev = "fedcba9876543210"
+sha256 = "def"
+"#,
+        )
+        .unwrap();
+        let graph = Graph::from_lockfile("app", &lockfile).unwrap();
+        assert_eq!(
+            graph.paths_to("nu-c").unwrap(),
+            vec![vec!["app", "nu-a", "nu-c"]]
+        );
+
+        let mut legacy = lockfile.clone();
+        legacy.version = 1;
+        assert!(Graph::from_lockfile("app", &legacy).is_none());
+    }
+
+    #[test]
+    fn renders_text_tree_with_shared_subtrees() {
+        let text = sample().render(Format::Text, None).unwrap();
+        assert_eq!(
+            text,
+            "app\n├── nu-a v1.0.0\n│   └── nu-c v0.3.0\n└── nu-b v2.0.0\n    └── nu-c v0.3.0\n"
+        );
+
+        let shallow = sample().render(Format::Text, Some(1)).unwrap();
+        assert_eq!(shallow, "app\n├── nu-a v1.0.0\n└── nu-b v2.0.0\n");
+    }
+
+    #[test]
+    fn inverts_and_finds_paths() {
+        let inverted = sample().inverted().render(Format::Text, None).unwrap();
+        assert_eq!(
+            inverted,
+            "nu-c v0.3.0\n├── nu-a v1.0.0\n│   └── app\n└── nu-b v2.0.0\n    └── app\n"
+        );
+
+        let paths = sample().paths_to("nu-c").unwrap();
+        assert_eq!(
+            paths,
+            vec![vec!["app", "nu-a", "nu-c"], vec!["app", "nu-b", "nu-c"]]
+        );
+        assert!(sample().paths_to("nu-missing").is_err());
+    }
+
+    #[test]
+    fn renders_dot_and_mermaid_edges() {
+        let dot = sample().render(Format::Dot, None).unwrap();
+        assert!(dot.contains("\"app\" -> \"nu-a\";"));
+        assert!(dot.contains("\"nu-b\" -> \"nu-c\";"));
+
+        let mermaid = sample().render(Format::Mermaid, None).unwrap();
+        assert!(mermaid.starts_with("graph TD\n"));
+        assert!(mermaid.contains("n0[\"app\"]"));
+        assert!(mermaid.contains("n0 --> n1"));
+    }
+}
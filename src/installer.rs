@@ -1,151 +1,587 @@
 use std::path::Path;
 
+use crate::cache;
 use crate::config::{self, GlobalConfig};
-use crate::error::Result;
-use crate::git;
-use crate::lockfile::{LockedPackage, Lockfile};
-use crate::manifest::Manifest;
-use crate::resolver::{self, ResolvedDep};
+use crate::error::{NuanceError, Result};
+use crate::git::{self, FetchTarget};
+use crate::jobs;
+use crate::lockfile::{LockedPackage, Lockfile, SourceKind};
+use crate::manifest::{DependencySpec, Manifest};
+use crate::progress;
+use crate::resolver::{self, ResolvedDep, Unlock};
 
 /// The name of the directory where local dependencies are installed.
 const MODULES_DIR: &str = ".nu_modules";
 
+/// Options controlling an install run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InstallOptions {
+    /// Use the lockfile only; error if it is missing.
+    pub frozen: bool,
+    /// Skip dev-dependencies (production-style install).
+    pub no_dev: bool,
+}
+
+/// Options controlling an update run.
+#[derive(Debug, Clone, Default)]
+pub struct UpdateOptions {
+    /// Packages to update; all packages when empty.
+    pub names: Vec<String>,
+    /// Also update the transitive dependencies of the named packages.
+    pub recursive: bool,
+    /// Move the single named package to this exact tag or commit.
+    pub precise: Option<String>,
+}
+
 /// Run a full local install: resolve → fetch → checksum → place → lock.
-pub fn install(project_dir: &Path, frozen: bool) -> Result<()> {
+pub fn install(project_dir: &Path, options: &InstallOptions) -> Result<()> {
     let manifest = Manifest::from_dir(project_dir)?;
+    let roots = manifest_roots(&manifest);
     let lock_path = project_dir.join("mod.lock");
     let modules_dir = project_dir.join(MODULES_DIR);
 
-    if manifest.dependencies.is_empty() {
-        eprintln!("No dependencies declared in mod.toml.");
+    if manifest.dependencies.is_empty() && manifest.dev_dependencies.is_empty() {
+        progress::println("No dependencies declared in mod.toml.");
         write_activate_overlay(&modules_dir, MODULES_DIR, std::iter::empty::<&str>())?;
         return Ok(());
     }
 
     // Determine whether to re-resolve or use the lockfile
-    let resolved = if frozen {
+    let resolved = if options.frozen {
         // --frozen: use lockfile only
         if !lock_path.exists() {
-            return Err(crate::error::NuanceError::Lockfile(
+            return Err(NuanceError::Lockfile(
                 "mod.lock not found (required with --frozen)".to_string(),
             ));
         }
         let lockfile = Lockfile::from_path(&lock_path)?;
-        eprintln!("Using locked dependencies (--frozen).");
-        resolver::resolve_from_lock(&lockfile.packages)
-    } else if lock_path.exists() && !is_lockfile_stale(project_dir)? {
-        // Lockfile exists and is up-to-date
+        progress::println("Using locked dependencies (--frozen).");
+        resolver::resolve_from_lock(&frozen_packages(&lockfile, &roots, "mod.lock")?)
+    } else if lock_path.exists() {
         let lockfile = Lockfile::from_path(&lock_path)?;
-        eprintln!("Using existing lockfile.");
-        resolver::resolve_from_lock(&lockfile.packages)
+        let roots = manifest
+            .dependencies
+            .iter()
+            .chain(manifest.dev_dependencies.iter());
+        if lockfile_is_fresh(&lockfile, roots) {
+            // Lockfile exists and is up-to-date
+            progress::println("Using existing lockfile.");
+            resolver::resolve_from_lock(&lockfile.packages)
+        } else {
+            // Re-resolve changed entries, keeping the rest at their locked commits
+            progress::println("Updating lockfile...");
+            resolve_project(project_dir, &manifest, Some(&lockfile), options.no_dev)?
+        }
     } else {
         // Resolve fresh
-        eprintln!("Resolving dependencies...");
-        resolver::resolve(project_dir)?
+        progress::println("Resolving dependencies...");
+        resolve_project(project_dir, &manifest, None, options.no_dev)?
     };
 
     // Install each dependency
-    install_resolved(&resolved, &modules_dir, &lock_path, MODULES_DIR)
+    install_resolved(
+        &resolved,
+        &roots,
+        project_dir,
+        &modules_dir,
+        &lock_path,
+        MODULES_DIR,
+        options.no_dev,
+    )
 }
 
-/// Run an update: always re-resolve, ignoring existing lockfile.
-pub fn update(project_dir: &Path) -> Result<()> {
+/// Resolve the project's dependencies, keeping packages in `lockfile` at
+/// their locked commits where their spec is unchanged.
+///
+/// With `no_dev`, dev-dependencies are not resolved at all, so an install
+/// for production never fetches them. Those already in `lockfile` stay
+/// locked as they were; others are locked by the next full install.
+fn resolve_project(
+    project_dir: &Path,
+    manifest: &Manifest,
+    lockfile: Option<&Lockfile>,
+    no_dev: bool,
+) -> Result<Vec<ResolvedDep>> {
+    let locked = lockfile.map_or(&[][..], |l| &l.packages[..]);
+    if !no_dev {
+        return resolver::resolve(project_dir, locked, &Unlock::default());
+    }
+
+    let mut resolved = resolver::resolve_without_dev(project_dir, locked, &Unlock::default())?;
+    if let Some(lockfile) = lockfile {
+        let dev_roots = manifest.dev_dependencies.keys().map(String::as_str);
+        let reachable = if lockfile.has_edges() {
+            lockfile.reachable_from(dev_roots)
+        } else {
+            dev_roots.collect()
+        };
+        let kept: Vec<LockedPackage> = lockfile
+            .packages
+            .iter()
+            .filter(|p| p.dev && reachable.contains(p.name.as_str()))
+            .filter(|p| !resolved.iter().any(|dep| dep.name == p.name))
+            .cloned()
+            .collect();
+        resolved.extend(resolver::resolve_from_lock(&kept));
+        resolved.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    Ok(resolved)
+}
+
+/// Run an update: re-resolve everything, or only the named packages while
+/// keeping the rest at their locked commits.
+pub fn update(project_dir: &Path, options: &UpdateOptions) -> Result<()> {
+    let manifest = Manifest::from_dir(project_dir)?;
+    let roots = manifest_roots(&manifest);
     let lock_path = project_dir.join("mod.lock");
-    // Remove existing lockfile to force re-resolution
-    if lock_path.exists() {
-        std::fs::remove_file(&lock_path)?;
+    let modules_dir = project_dir.join(MODULES_DIR);
+
+    if manifest.dependencies.is_empty() && manifest.dev_dependencies.is_empty() {
+        progress::println("No dependencies declared in mod.toml.");
+        write_activate_overlay(&modules_dir, MODULES_DIR, std::iter::empty::<&str>())?;
+        return Ok(());
+    }
+
+    let previous = read_previous_lock(&lock_path, "mod.lock", options)?;
+    let (locked, unlock) = update_plan(&previous, options);
+    progress::println("Updating dependencies...");
+    let resolved = resolver::resolve(project_dir, locked, &unlock)?;
+    report_changes(&previous, &resolved);
+
+    install_resolved(
+        &resolved,
+        &roots,
+        project_dir,
+        &modules_dir,
+        &lock_path,
+        MODULES_DIR,
+        false,
+    )
+}
+
+/// Run an update of the global dependencies, like [`update`].
+pub fn update_global(options: &UpdateOptions) -> Result<()> {
+    let config = GlobalConfig::load()?;
+    let roots: Vec<String> = config.dependencies.keys().cloned().collect();
+    let modules_dir = config.modules_dir()?;
+    let lock_path = config::global_lock_path()?;
+    let config_dir = config::global_config_dir()?;
+    let display_dir = modules_dir.display().to_string();
+
+    if config.dependencies.is_empty() {
+        progress::println("No dependencies declared in global config.");
+        write_activate_overlay(&modules_dir, &display_dir, std::iter::empty::<&str>())?;
+        return Ok(());
+    }
+
+    let previous = read_previous_lock(&lock_path, "config.lock", options)?;
+    let (locked, unlock) = update_plan(&previous, options);
+    progress::println("Updating global dependencies...");
+    let resolved = resolver::resolve_from_deps(&config.dependencies, &config_dir, locked, &unlock)?;
+    report_changes(&previous, &resolved);
+
+    install_resolved(
+        &resolved,
+        &roots,
+        &config_dir,
+        &modules_dir,
+        &lock_path,
+        &display_dir,
+        false,
+    )
+}
+
+/// Load the lockfile an update starts from, checking that every package
+/// named for a selective update is in it.
+fn read_previous_lock(
+    lock_path: &Path,
+    lock_name: &str,
+    options: &UpdateOptions,
+) -> Result<Vec<LockedPackage>> {
+    if options.precise.is_some() && options.names.len() != 1 {
+        return Err(NuanceError::Other(
+            "--precise requires exactly one package name".to_string(),
+        ));
+    }
+
+    if !lock_path.exists() {
+        if options.names.is_empty() {
+            return Ok(Vec::new());
+        }
+        return Err(NuanceError::Lockfile(format!(
+            "{lock_name} not found; run `nuance install` before updating individual packages"
+        )));
+    }
+
+    let lockfile = Lockfile::from_path(lock_path)?;
+    for name in &options.names {
+        if lockfile.find_package(name).is_none() {
+            return Err(NuanceError::Other(format!(
+                "package '{name}' not found in {lock_name}"
+            )));
+        }
+    }
+    Ok(lockfile.packages)
+}
+
+/// Decide which locked packages the resolver may keep for an update.
+///
+/// Without names everything is re-resolved; with names, only those (and,
+/// with `recursive`, their dependencies) are unlocked.
+fn update_plan<'a>(
+    previous: &'a [LockedPackage],
+    options: &UpdateOptions,
+) -> (&'a [LockedPackage], Unlock) {
+    if options.names.is_empty() {
+        return (&[], Unlock::default());
+    }
+    let unlock = Unlock {
+        names: options.names.iter().cloned().collect(),
+        recursive: options.recursive,
+        precise: options.precise.clone(),
+    };
+    (previous, unlock)
+}
+
+/// Print every package whose locked version moved, as `old -> new`.
+fn report_changes(previous: &[LockedPackage], resolved: &[ResolvedDep]) {
+    let mut changed = 0;
+    for dep in resolved {
+        let Some(rev) = &dep.rev else { continue };
+        match previous.iter().find(|p| p.name == dep.name) {
+            Some(old) if old.rev.as_ref() == Some(rev) && old.tag == dep.tag => {}
+            Some(old) => {
+                let old_label = version_label(old.tag.as_deref(), old.rev.as_deref());
+                let new_label = version_label(dep.tag.as_deref(), Some(rev));
+                progress::println(&format!("  {}: {old_label} -> {new_label}", dep.name));
+                changed += 1;
+            }
+            None => {
+                let label = version_label(dep.tag.as_deref(), Some(rev));
+                progress::println(&format!("  {}: added {label}", dep.name));
+                changed += 1;
+            }
+        }
+    }
+    if changed == 0 {
+        progress::println("All packages are up to date.");
+    }
+}
+
+/// A short, human-readable name for a locked version: its tag, or an
+/// abbreviated commit.
+fn version_label(tag: Option<&str>, rev: Option<&str>) -> String {
+    match (tag, rev) {
+        (Some(tag), _) => tag.to_string(),
+        (None, Some(rev)) => rev[..7.min(rev.len())].to_string(),
+        (None, None) => "(path)".to_string(),
     }
-    install(project_dir, false)
 }
 
 /// Run a global install: resolve from `~/.config/nuance/config.toml` and install
 /// modules to the global modules directory.
-pub fn install_global(frozen: bool) -> Result<()> {
+///
+/// The global config has no dev-dependencies, so `no_dev` has no effect.
+pub fn install_global(options: &InstallOptions) -> Result<()> {
     let config = GlobalConfig::load()?;
+    let roots: Vec<String> = config.dependencies.keys().cloned().collect();
     let modules_dir = config.modules_dir()?;
     let lock_path = config::global_lock_path()?;
+    let config_dir = config::global_config_dir()?;
     let display_dir = modules_dir.display().to_string();
 
     if config.dependencies.is_empty() {
-        eprintln!("No dependencies declared in global config.");
+        progress::println("No dependencies declared in global config.");
         write_activate_overlay(&modules_dir, &display_dir, std::iter::empty::<&str>())?;
         return Ok(());
     }
 
-    let resolved = if frozen {
+    let resolved = if options.frozen {
         if !lock_path.exists() {
-            return Err(crate::error::NuanceError::Lockfile(
+            return Err(NuanceError::Lockfile(
                 "config.lock not found (required with --frozen)".to_string(),
             ));
         }
         let lockfile = Lockfile::from_path(&lock_path)?;
-        eprintln!("Using locked global dependencies (--frozen).");
-        resolver::resolve_from_lock(&lockfile.packages)
-    } else if lock_path.exists() && !is_global_lockfile_stale(&config, &lock_path)? {
+        progress::println("Using locked global dependencies (--frozen).");
+        resolver::resolve_from_lock(&frozen_packages(&lockfile, &roots, "config.lock")?)
+    } else if lock_path.exists() {
         let lockfile = Lockfile::from_path(&lock_path)?;
-        eprintln!("Using existing global lockfile.");
-        resolver::resolve_from_lock(&lockfile.packages)
+        if lockfile_is_fresh(&lockfile, &config.dependencies) {
+            progress::println("Using existing global lockfile.");
+            resolver::resolve_from_lock(&lockfile.packages)
+        } else {
+            progress::println("Updating global lockfile...");
+            resolver::resolve_from_deps(
+                &config.dependencies,
+                &config_dir,
+                &lockfile.packages,
+                &Unlock::default(),
+            )?
+        }
     } else {
-        eprintln!("Resolving global dependencies...");
-        resolver::resolve_from_deps(&config.dependencies)?
+        progress::println("Resolving global dependencies...");
+        resolver::resolve_from_deps(&config.dependencies, &config_dir, &[], &Unlock::default())?
     };
 
-    install_resolved(&resolved, &modules_dir, &lock_path, &display_dir)
+    install_resolved(
+        &resolved,
+        &roots,
+        &config_dir,
+        &modules_dir,
+        &lock_path,
+        &display_dir,
+        false,
+    )
 }
 
 /// Install a list of resolved dependencies into a target directory and write the lockfile.
+///
+/// Packages from the previous lockfile that are no longer resolved are
+/// removed from the target directory.
+///
+/// Path dependencies are resolved relative to `base_dir`. With `skip_dev`,
+/// dev-only packages are not installed but still kept in the lockfile.
 fn install_resolved(
     resolved: &[ResolvedDep],
+    roots: &[String],
+    base_dir: &Path,
     modules_dir: &Path,
     lock_path: &Path,
     display_name: &str,
+    skip_dev: bool,
 ) -> Result<()> {
     std::fs::create_dir_all(modules_dir)?;
+    let previous = if lock_path.exists() {
+        Lockfile::from_path(lock_path).ok()
+    } else {
+        None
+    };
     let mut locked_packages = Vec::new();
+    let mut installed = Vec::new();
+
+    if let Some(previous) = &previous {
+        for old in &previous.packages {
+            if !resolved.iter().any(|dep| dep.name == old.name) {
+                progress::println(&format!("  Removing {}...", old.name));
+                remove_existing(&modules_dir.join(&old.name))?;
+            }
+        }
+    }
+
+    let to_install: Vec<&ResolvedDep> = resolved.iter().filter(|d| !(skip_dev && d.dev)).collect();
+    fetch_missing_commits(&to_install)?;
+
+    // Export and checksum in parallel; results (and output) stay in order
+    let mut results = jobs::map(&to_install, |dep| {
+        install_dep(dep, base_dir, modules_dir)?;
+        resolver::compute_checksum(&modules_dir.join(&dep.name))
+    })
+    .into_iter();
 
     for dep in resolved {
-        eprintln!(
-            "  Installing {}@{}...",
-            dep.name,
-            &dep.rev[..12.min(dep.rev.len())]
-        );
-        install_dep(dep, modules_dir)?;
+        if skip_dev && dep.dev {
+            remove_existing(&modules_dir.join(&dep.name))?;
+            let sha256 = match previous.as_ref().and_then(|l| l.find_package(&dep.name)) {
+                Some(locked) if same_source(locked, dep) => locked.sha256.clone(),
+                _ => cached_checksum(dep, base_dir)?,
+            };
+            locked_packages.push(locked_package(dep, sha256));
+            continue;
+        }
 
-        let dest = modules_dir.join(&dep.name);
-        let sha256 = resolver::compute_checksum(&dest)?;
+        match (&dep.rev, &dep.path) {
+            (Some(rev), _) => progress::println(&format!(
+                "  Installing {}@{}...",
+                dep.name,
+                &rev[..12.min(rev.len())]
+            )),
+            (None, Some(path)) => {
+                progress::println(&format!("  Linking {} from {path}...", dep.name))
+            }
+            (None, None) => {}
+        }
+        let sha256 = results.next().expect("one result per installed package")?;
 
-        locked_packages.push(LockedPackage {
-            name: dep.name.clone(),
-            git: dep.git.clone(),
-            tag: dep.tag.clone(),
-            rev: dep.rev.clone(),
-            sha256,
-        });
+        locked_packages.push(locked_package(dep, sha256));
+        installed.push(dep.name.as_str());
     }
 
     // Write lockfile
-    let lockfile = Lockfile {
-        version: 1,
-        packages: locked_packages,
-    };
+    let lockfile = Lockfile::new(roots.to_vec(), locked_packages);
     lockfile.write_to(lock_path)?;
+    cache::remember_lockfile(lock_path)?;
 
-    eprintln!(
+    // Keep the git cache under `cache_max_size`, sparing this install's repos
+    let in_use: Vec<&str> = resolved.iter().filter_map(|d| d.git.as_deref()).collect();
+    for entry in cache::enforce_limit(&in_use)? {
+        progress::println(&format!(
+            "  Evicted {} from the git cache ({})",
+            entry.url.as_deref().unwrap_or("unknown repo"),
+            cache::format_size(entry.size)
+        ));
+    }
+
+    progress::println(&format!(
         "\nInstalled {} package{} into {}/",
-        resolved.len(),
-        if resolved.len() == 1 { "" } else { "s" },
+        installed.len(),
+        if installed.len() == 1 { "" } else { "s" },
         display_name
-    );
+    ));
+    let skipped = resolved.len() - installed.len();
+    if skipped > 0 {
+        progress::println(&format!(
+            "Skipped {skipped} dev-dependenc{} (--no-dev)",
+            if skipped == 1 { "y" } else { "ies" }
+        ));
+    }
 
-    write_activate_overlay(
-        modules_dir,
-        display_name,
-        resolved.iter().map(|dep| dep.name.as_str()),
-    )?;
+    write_activate_overlay(modules_dir, display_name, installed)?;
 
     Ok(())
 }
 
+/// Build the lockfile entry for a resolved dependency.
+fn locked_package(dep: &ResolvedDep, sha256: String) -> LockedPackage {
+    LockedPackage {
+        name: dep.name.clone(),
+        source: if dep.path.is_some() {
+            SourceKind::Path
+        } else {
+            SourceKind::Git
+        },
+        git: dep.git.clone(),
+        path: dep.path.clone(),
+        subdir: dep.subdir.clone(),
+        branch: dep.requested.as_ref().and_then(|r| r.branch.clone()),
+        tag: dep.tag.clone(),
+        rev: dep.rev.clone(),
+        sha256,
+        dev: dep.dev,
+        dependencies: dep.dependencies.clone(),
+        requested: dep.requested.clone(),
+    }
+}
+
+/// The root dependency names of a manifest, including dev-dependencies.
+fn manifest_roots(manifest: &Manifest) -> Vec<String> {
+    manifest
+        .dependencies
+        .keys()
+        .chain(manifest.dev_dependencies.keys())
+        .cloned()
+        .collect()
+}
+
+/// Select the locked packages a `--frozen` install should use.
+///
+/// Every root must be locked. When the lockfile records dependency edges,
+/// packages no longer reachable from the roots are pruned.
+fn frozen_packages(
+    lockfile: &Lockfile,
+    roots: &[String],
+    lock_name: &str,
+) -> Result<Vec<LockedPackage>> {
+    for name in roots {
+        if lockfile.find_package(name).is_none() {
+            return Err(NuanceError::Lockfile(format!(
+                "{lock_name} is out of date: '{name}' is not locked (run `nuance install`)"
+            )));
+        }
+    }
+    if !lockfile.has_edges() {
+        return Ok(lockfile.packages.clone());
+    }
+
+    let reachable = lockfile.reachable_from(roots.iter().map(String::as_str));
+    let mut packages = Vec::new();
+    for pkg in &lockfile.packages {
+        if reachable.contains(pkg.name.as_str()) {
+            packages.push(pkg.clone());
+        } else {
+            progress::println(&format!("  Pruning {} (no longer required)", pkg.name));
+        }
+    }
+    Ok(packages)
+}
+
+/// Make sure the git cache holds every commit about to be installed,
+/// fetching each repository at most once and several at a time.
+///
+/// Commits already in the cache are never fetched. In offline mode, every
+/// missing commit is reported at once.
+fn fetch_missing_commits(deps: &[&ResolvedDep]) -> Result<()> {
+    if git::is_offline() {
+        let mut missing = Vec::new();
+        for dep in deps {
+            let (Some(url), Some(rev)) = (&dep.git, &dep.rev) else {
+                continue;
+            };
+            if !git::has_commit(&git::cached_repo(url)?, rev) {
+                missing.push(format!(
+                    "{} ({url} @ {})",
+                    dep.name,
+                    &rev[..12.min(rev.len())]
+                ));
+            }
+        }
+        if !missing.is_empty() {
+            return Err(NuanceError::Offline { missing });
+        }
+        return Ok(());
+    }
+
+    let mut repos: Vec<(&str, Vec<FetchTarget>)> = Vec::new();
+    for dep in deps {
+        let (Some(url), Some(rev)) = (&dep.git, &dep.rev) else {
+            continue;
+        };
+        if git::has_commit(&git::cached_repo(url)?, rev) {
+            continue;
+        }
+        let target = FetchTarget::commit(rev);
+        match repos.iter_mut().find(|(u, _)| u == url) {
+            Some((_, targets)) => targets.push(target),
+            None => repos.push((url, vec![target])),
+        }
+    }
+
+    let results = jobs::map(&repos, |(url, targets)| {
+        git::fetch_refs(url, targets).map(drop)
+    });
+    results.into_iter().collect()
+}
+
+/// Whether a lockfile entry points at exactly the same content as `dep`.
+fn same_source(locked: &LockedPackage, dep: &ResolvedDep) -> bool {
+    locked.git == dep.git
+        && locked.path == dep.path
+        && locked.subdir == dep.subdir
+        && locked.rev == dep.rev
+}
+
+/// Compute the checksum of a dependency from its source, without installing
+/// it or touching the network.
+///
+/// A git commit that isn't cached is locked without a checksum; the next
+/// install that includes it fills one in.
+fn cached_checksum(dep: &ResolvedDep, base_dir: &Path) -> Result<String> {
+    match (&dep.git, &dep.rev, &dep.path) {
+        (Some(url), Some(rev), _) => {
+            let repo_path = git::cached_repo(url)?;
+            if !git::has_commit(&repo_path, rev) {
+                return Ok(String::new());
+            }
+            git::checksum_at(&repo_path, rev, dep.subdir.as_deref())
+        }
+        (None, None, Some(path)) => resolver::compute_checksum(&base_dir.join(path)),
+        _ => Err(NuanceError::Lockfile(format!(
+            "package '{}' must have either a git URL and rev, or a path",
+            dep.name
+        ))),
+    }
+}
+
 fn write_activate_overlay<I, S>(
     modules_dir: &Path,
     display_name: &str,
@@ -171,96 +607,131 @@ where
     activate_script.push_str("\nexport alias deactivate = overlay hide activate\n");
 
     std::fs::write(&activate_path, activate_script)?;
-    eprintln!("Generated {}/activate.nu", display_name);
+    progress::println(&format!("Generated {}/activate.nu", display_name));
     Ok(())
 }
 
 /// Install a single resolved dependency into the modules directory.
-fn install_dep(dep: &ResolvedDep, modules_dir: &Path) -> Result<()> {
-    let repo_path = git::clone_or_fetch(&dep.git)?;
-    let dest = modules_dir.join(&dep.name);
-    git::export_to(&repo_path, &dep.rev, &dest)?;
-    Ok(())
-}
-
-/// Check if the lockfile is stale relative to mod.toml.
 ///
-/// A simple heuristic: if the dependency names in mod.toml don't match
-/// the locked package names, the lockfile is stale.
-fn is_lockfile_stale(project_dir: &Path) -> Result<bool> {
-    let manifest = Manifest::from_dir(project_dir)?;
-    let lock_path = project_dir.join("mod.lock");
-
-    if !lock_path.exists() {
-        return Ok(true);
-    }
-
-    let lockfile = Lockfile::from_path(&lock_path)?;
+/// Git dependencies are exported at their resolved commit; path dependencies
+/// are linked (or copied where symlinks are unavailable).
+fn install_dep(dep: &ResolvedDep, base_dir: &Path, modules_dir: &Path) -> Result<()> {
+    let dest = modules_dir.join(&dep.name);
+    remove_existing(&dest)?;
 
-    // Check if all manifest deps are in the lockfile
-    for name in manifest.dependencies.keys() {
-        if lockfile.find_package(name).is_none() {
-            return Ok(true); // New dep not in lockfile
+    match (&dep.git, &dep.rev, &dep.path) {
+        (Some(url), Some(rev), _) => {
+            let repo_path = git::ensure_commit(url, rev)?;
+            git::export_to(&repo_path, rev, dep.subdir.as_deref(), &dest)?;
+        }
+        (None, None, Some(path)) => {
+            let source = base_dir.join(path);
+            if !source.is_dir() {
+                return Err(NuanceError::Other(format!(
+                    "path dependency '{}' not found at {}",
+                    dep.name,
+                    source.display()
+                )));
+            }
+            link_or_copy(&source, &dest)?;
+        }
+        _ => {
+            return Err(NuanceError::Lockfile(format!(
+                "package '{}' must have either a git URL and rev, or a path",
+                dep.name
+            )));
         }
     }
+    Ok(())
+}
 
-    // Check if lockfile has deps not in manifest
-    for pkg in &lockfile.packages {
-        if !manifest.dependencies.contains_key(&pkg.name) {
-            return Ok(true); // Removed dep still in lockfile
-        }
+/// Remove whatever currently occupies `path`: a directory, file, or symlink.
+fn remove_existing(path: &Path) -> Result<()> {
+    let Ok(metadata) = std::fs::symlink_metadata(path) else {
+        return Ok(());
+    };
+    if metadata.is_dir() {
+        std::fs::remove_dir_all(path)?;
+    } else {
+        std::fs::remove_file(path)?;
     }
+    Ok(())
+}
 
-    Ok(false)
+/// Symlink a path dependency into place.
+#[cfg(unix)]
+fn link_or_copy(source: &Path, dest: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(source.canonicalize()?, dest)?;
+    Ok(())
 }
 
-/// Check if the global lockfile is stale relative to the global config.
-fn is_global_lockfile_stale(config: &GlobalConfig, lock_path: &Path) -> Result<bool> {
-    if !lock_path.exists() {
-        return Ok(true);
+/// Copy a path dependency into place (symlinking directories needs extra
+/// privileges on Windows).
+#[cfg(not(unix))]
+fn link_or_copy(source: &Path, dest: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(source)
+        .into_iter()
+        .filter_entry(|e| e.file_name() != ".git")
+    {
+        let entry = entry.map_err(|e| NuanceError::Other(e.to_string()))?;
+        let rel = entry.path().strip_prefix(source).unwrap_or(entry.path());
+        let target = dest.join(rel);
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
     }
+    Ok(())
+}
 
-    let lockfile = Lockfile::from_path(lock_path)?;
+/// Check whether the lockfile can be used as-is for the given root dependencies.
+///
+/// It is fresh when every root dependency is locked from the same spec that
+/// is requested now, and every entry is reachable from the roots (or, for
+/// version 1 lockfiles without edges, it holds exactly the roots).
+/// Otherwise the changed entries are reported and the caller re-resolves,
+/// keeping unchanged entries locked.
+fn lockfile_is_fresh<'a, I>(lockfile: &Lockfile, roots: I) -> bool
+where
+    I: IntoIterator<Item = (&'a String, &'a DependencySpec)>,
+{
+    let mut fresh = true;
+    let mut names = Vec::new();
 
-    for name in config.dependencies.keys() {
-        if lockfile.find_package(name).is_none() {
-            return Ok(true);
+    for (name, spec) in roots {
+        names.push(name.as_str());
+        match lockfile.find_package(name) {
+            None => {
+                progress::println(&format!("  {name}: not in lockfile"));
+                fresh = false;
+            }
+            Some(locked) if !locked.locked_from(spec) => {
+                progress::println(&format!("  {name}: requirement changed"));
+                fresh = false;
+            }
+            Some(_) => {}
         }
     }
 
-    for pkg in &lockfile.packages {
-        if !config.dependencies.contains_key(&pkg.name) {
-            return Ok(true);
-        }
+    if !lockfile.has_edges() {
+        // Without edges, extra entries may be removed or transitive dependencies;
+        // re-resolving against the lock sorts out which without moving anything.
+        return fresh && lockfile.packages.len() == names.len();
     }
 
-    Ok(false)
+    // Entries no longer reachable from the roots are orphans to drop
+    fresh && lockfile.reachable_from(names).len() == lockfile.packages.len()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    fn make_temp_dir(label: &str) -> PathBuf {
-        let unique = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-        let dir = std::env::temp_dir().join(format!(
-            "nuance_installer_test_{}_{}_{}",
-            label,
-            std::process::id(),
-            unique
-        ));
-        std::fs::create_dir_all(&dir).unwrap();
-        dir
-    }
+    use crate::test_support::temp_dir;
 
     #[test]
     fn writes_activate_overlay_with_modules() {
-        let modules_dir = make_temp_dir("with_modules");
+        let modules_dir = temp_dir("with_modules");
 
         write_activate_overlay(&modules_dir, ".nu_modules", ["nu-foo", "nu-bar"]).unwrap();
 
@@ -274,7 +745,7 @@ mod tests {
 
     #[test]
     fn writes_activate_overlay_without_modules() {
-        let modules_dir = make_temp_dir("without_modules");
+        let modules_dir = temp_dir("without_modules");
 
         write_activate_overlay(&modules_dir, ".nu_modules", std::iter::empty::<&str>()).unwrap();
 
@@ -287,7 +758,7 @@ mod tests {
 
     #[test]
     fn frozen_install_without_dependencies_writes_activate_overlay() {
-        let project_dir = make_temp_dir("empty_manifest");
+        let project_dir = temp_dir("empty_manifest");
         std::fs::write(
             project_dir.join("mod.toml"),
             r#"[package]
@@ -297,7 +768,14 @@ version = "0.1.0"
         )
         .unwrap();
 
-        install(&project_dir, true).unwrap();
+        install(
+            &project_dir,
+            &InstallOptions {
+                frozen: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
 
         let activate =
             std::fs::read_to_string(project_dir.join(".nu_modules").join("activate.nu")).unwrap();
@@ -306,4 +784,392 @@ version = "0.1.0"
 
         let _ = std::fs::remove_dir_all(project_dir);
     }
+
+    #[test]
+    fn installs_path_dependency_and_locks_it_without_rev() {
+        let workspace = temp_dir("path_dep");
+        let project_dir = workspace.join("app");
+        let common_dir = workspace.join("nu-common");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::create_dir_all(&common_dir).unwrap();
+        std::fs::write(
+            common_dir.join("mod.toml"),
+            "[package]\nname = \"nu-common\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::write(common_dir.join("mod.nu"), "export def hello [] { 'hi' }\n").unwrap();
+        std::fs::write(
+            project_dir.join("mod.toml"),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+nu-common = { path = "../nu-common" }
+"#,
+        )
+        .unwrap();
+
+        install(&project_dir, &InstallOptions::default()).unwrap();
+
+        let installed = project_dir.join(".nu_modules").join("nu-common");
+        assert!(installed.join("mod.nu").exists());
+
+        let lock = Lockfile::from_path(&project_dir.join("mod.lock")).unwrap();
+        let pkg = lock.find_package("nu-common").unwrap();
+        assert_eq!(pkg.path.as_deref(), Some("../nu-common"));
+        assert!(pkg.git.is_none());
+        assert!(pkg.rev.is_none());
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn no_dev_skips_dev_dependencies_but_keeps_them_locked() {
+        let workspace = temp_dir("no_dev");
+        let project_dir = workspace.join("app");
+        for (dir, name) in [("nu-common", "nu-common"), ("nu-assert", "nu-assert")] {
+            let dep_dir = workspace.join(dir);
+            std::fs::create_dir_all(&dep_dir).unwrap();
+            std::fs::write(
+                dep_dir.join("mod.toml"),
+                format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n"),
+            )
+            .unwrap();
+        }
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::write(
+            project_dir.join("mod.toml"),
+            r#"[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+nu-common = { path = "../nu-common" }
+
+[dev-dependencies]
+nu-assert = { path = "../nu-assert" }
+"#,
+        )
+        .unwrap();
+
+        install(&project_dir, &InstallOptions::default()).unwrap();
+        let modules_dir = project_dir.join(".nu_modules");
+        assert!(modules_dir.join("nu-assert").exists());
+
+        install(
+            &project_dir,
+            &InstallOptions {
+                no_dev: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(modules_dir.join("nu-common").exists());
+        assert!(!modules_dir.join("nu-assert").exists());
+
+        let activate = std::fs::read_to_string(modules_dir.join("activate.nu")).unwrap();
+        assert!(activate.contains("export use nu-common *"));
+        assert!(!activate.contains("nu-assert"));
+
+        let lock = Lockfile::from_path(&project_dir.join("mod.lock")).unwrap();
+        assert!(lock.find_package("nu-assert").unwrap().dev);
+        assert!(!lock.find_package("nu-common").unwrap().dev);
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn no_dev_locks_skipped_git_dependencies_without_fetching() {
+        let workspace = temp_dir("no_dev_git");
+        let project_dir = workspace.join("app");
+        std::fs::create_dir_all(&project_dir).unwrap();
+        // Nothing can be fetched from this host
+        let url = "https://nuance.invalid/nu-assert";
+        std::fs::write(
+            project_dir.join("mod.toml"),
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dev-dependencies]\nnu-assert = {{ git = \"{url}\", tag = \"v1.0.0\" }}\n"
+            ),
+        )
+        .unwrap();
+        let rev = "0123456789abcdef0123456789abcdef01234567";
+        std::fs::write(
+            project_dir.join("mod.lock"),
+            format!(
+                "version = 2\nroots = [\"nu-assert\"]\n\n[[package]]\nname = \"nu-assert\"\nsource = \"git\"\ngit = \"{url}\"\ntag = \"v1.0.0\"\nrev = \"{rev}\"\nsha256 = \"locked\"\ndev = true\n\n[package.requested]\ngit = \"{url}\"\ntag = \"v1.0.0\"\n"
+            ),
+        )
+        .unwrap();
+
+        let options = InstallOptions {
+            no_dev: true,
+            ..Default::default()
+        };
+        install(&project_dir, &options).unwrap();
+        let lock = Lockfile::from_path(&project_dir.join("mod.lock")).unwrap();
+        let pkg = lock.find_package("nu-assert").unwrap();
+        assert_eq!(pkg.sha256, "locked");
+        assert_eq!(pkg.rev.as_deref(), Some(rev));
+
+        // A commit the lock doesn't know and the cache doesn't hold
+        let moved = ResolvedDep {
+            name: "nu-assert".to_string(),
+            git: Some(url.to_string()),
+            path: None,
+            subdir: None,
+            tag: Some("v1.1.0".to_string()),
+            rev: Some("fedcba9876543210fedcba9876543210fedcba98".to_string()),
+            dev: true,
+            requested: None,
+            dependencies: Vec::new(),
+        };
+        assert_eq!(cached_checksum(&moved, &project_dir).unwrap(), "");
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn no_dev_resolves_without_fetching_dev_dependencies() {
+        let workspace = temp_dir("no_dev_resolve");
+        let project_dir = workspace.join("app");
+        for name in ["nu-common", "nu-extra"] {
+            let dep_dir = workspace.join(name);
+            std::fs::create_dir_all(&dep_dir).unwrap();
+            std::fs::write(dep_dir.join("mod.nu"), format!("# {name}\n")).unwrap();
+        }
+        std::fs::create_dir_all(&project_dir).unwrap();
+        // Nothing can be fetched from this host
+        let url = "https://nuance.invalid/nu-assert";
+        let manifest = |deps: &str| {
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}\n[dev-dependencies]\nnu-assert = {{ git = \"{url}\", tag = \"v1.0.0\" }}\n"
+            )
+        };
+        let options = InstallOptions {
+            no_dev: true,
+            ..Default::default()
+        };
+
+        // No lockfile yet
+        std::fs::write(
+            project_dir.join("mod.toml"),
+            manifest("nu-common = { path = \"../nu-common\" }\n"),
+        )
+        .unwrap();
+        install(&project_dir, &options).unwrap();
+        assert!(project_dir.join(".nu_modules/nu-common").exists());
+        let lock_path = project_dir.join("mod.lock");
+        assert!(
+            Lockfile::from_path(&lock_path)
+                .unwrap()
+                .find_package("nu-assert")
+                .is_none()
+        );
+
+        // A stale lockfile keeps its dev entries as they were
+        let mut lockfile = Lockfile::from_path(&lock_path).unwrap();
+        let rev = "0123456789abcdef0123456789abcdef01234567";
+        lockfile.packages.push(LockedPackage {
+            name: "nu-assert".to_string(),
+            source: SourceKind::Git,
+            git: Some(url.to_string()),
+            path: None,
+            subdir: None,
+            branch: None,
+            tag: Some("v1.0.0".to_string()),
+            rev: Some(rev.to_string()),
+            sha256: "locked".to_string(),
+            dev: true,
+            dependencies: Vec::new(),
+            requested: None,
+        });
+        lockfile.write_to(&lock_path).unwrap();
+        std::fs::write(
+            project_dir.join("mod.toml"),
+            manifest(
+                "nu-common = { path = \"../nu-common\" }\nnu-extra = { path = \"../nu-extra\" }\n",
+            ),
+        )
+        .unwrap();
+        install(&project_dir, &options).unwrap();
+        assert!(project_dir.join(".nu_modules/nu-extra").exists());
+        let lockfile = Lockfile::from_path(&lock_path).unwrap();
+        let pkg = lockfile.find_package("nu-assert").unwrap();
+        assert_eq!(pkg.rev.as_deref(), Some(rev));
+        assert_eq!(pkg.sha256, "locked");
+        assert!(pkg.dev);
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn install_relocks_dependency_whose_spec_changed() {
+        let workspace = temp_dir("relock");
+        let project_dir = workspace.join("app");
+        for name in ["nu-a", "nu-b-v1", "nu-b-v2"] {
+            let dep_dir = workspace.join(name);
+            std::fs::create_dir_all(&dep_dir).unwrap();
+            std::fs::write(dep_dir.join("mod.nu"), format!("# {name}\n")).unwrap();
+        }
+        std::fs::create_dir_all(&project_dir).unwrap();
+        let manifest = |b_dir: &str| {
+            format!(
+                "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nnu-a = {{ path = \"../nu-a\" }}\nnu-b = {{ path = \"../{b_dir}\" }}\n"
+            )
+        };
+
+        std::fs::write(project_dir.join("mod.toml"), manifest("nu-b-v1")).unwrap();
+        install(&project_dir, &InstallOptions::default()).unwrap();
+
+        std::fs::write(project_dir.join("mod.toml"), manifest("nu-b-v2")).unwrap();
+        install(&project_dir, &InstallOptions::default()).unwrap();
+
+        let lock = Lockfile::from_path(&project_dir.join("mod.lock")).unwrap();
+        assert_eq!(
+            lock.find_package("nu-b").unwrap().path.as_deref(),
+            Some("../nu-b-v2")
+        );
+        assert_eq!(
+            lock.find_package("nu-a").unwrap().path.as_deref(),
+            Some("../nu-a")
+        );
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn changed_spec_makes_lockfile_stale() {
+        let spec = DependencySpec {
+            git: Some("https://github.com/user/nu-utils".to_string()),
+            tag: Some("v1.0.0".to_string()),
+            ..Default::default()
+        };
+        let locked = |name: &str, dependencies: &[&str]| LockedPackage {
+            name: name.to_string(),
+            source: SourceKind::Git,
+            git: spec.git.clone(),
+            path: None,
+            subdir: None,
+            branch: None,
+            tag: spec.tag.clone(),
+            rev: Some("aaaa".to_string()),
+            sha256: "abc".to_string(),
+            dev: false,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            requested: Some(spec.clone()),
+        };
+        let name = "nu-utils".to_string();
+        let lockfile = Lockfile::new(
+            vec![name.clone()],
+            vec![locked("nu-utils", &["nu-child"]), locked("nu-child", &[])],
+        );
+
+        assert!(lockfile_is_fresh(&lockfile, [(&name, &spec)]));
+
+        let bumped = DependencySpec {
+            tag: Some("v2.0.0".to_string()),
+            ..spec.clone()
+        };
+        assert!(!lockfile_is_fresh(&lockfile, [(&name, &bumped)]));
+
+        let forked = DependencySpec {
+            git: Some("https://github.com/fork/nu-utils".to_string()),
+            ..spec.clone()
+        };
+        assert!(!lockfile_is_fresh(&lockfile, [(&name, &forked)]));
+
+        // An entry nothing depends on any more is an orphan
+        let mut orphaned = lockfile.clone();
+        orphaned.packages.push(locked("nu-orphan", &[]));
+        assert!(!lockfile_is_fresh(&orphaned, [(&name, &spec)]));
+
+        let other = "nu-other".to_string();
+        assert!(!lockfile_is_fresh(
+            &lockfile,
+            [(&name, &spec), (&other, &spec)]
+        ));
+    }
+
+    #[test]
+    fn lockfile_without_requested_specs_stays_fresh() {
+        let lockfile = Lockfile::from_str(
+            r#"version = 1
+
+[[package]]
+name = "nu-utils"
+git = "https://github.com/user/nu-utils"
+subdir = "modules/utils"
+tag = "v1.2.0"
+rev = "d4e8f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8"
+sha256 = "abc123"
+"#,
+        )
+        .unwrap();
+        let name = "nu-utils".to_string();
+        let spec = DependencySpec {
+            git: Some("https://github.com/user/nu-utils".to_string()),
+            subdir: Some("modules/utils".to_string()),
+            tag: Some("v1.2.0".to_string()),
+            ..Default::default()
+        };
+        assert!(lockfile_is_fresh(&lockfile, [(&name, &spec)]));
+
+        let range = DependencySpec {
+            tag: None,
+            version: Some("^1.1".to_string()),
+            ..spec.clone()
+        };
+        assert!(lockfile_is_fresh(&lockfile, [(&name, &range)]));
+
+        let bumped = DependencySpec {
+            version: Some("^2".to_string()),
+            ..range.clone()
+        };
+        assert!(!lockfile_is_fresh(&lockfile, [(&name, &bumped)]));
+
+        let moved = DependencySpec {
+            subdir: None,
+            ..spec.clone()
+        };
+        assert!(!lockfile_is_fresh(&lockfile, [(&name, &moved)]));
+    }
+
+    #[test]
+    fn selective_update_requires_locked_package_names() {
+        let workspace = temp_dir("selective_update");
+        let project_dir = workspace.join("app");
+        let dep_dir = workspace.join("nu-a");
+        std::fs::create_dir_all(&dep_dir).unwrap();
+        std::fs::write(dep_dir.join("mod.nu"), "# nu-a\n").unwrap();
+        std::fs::create_dir_all(&project_dir).unwrap();
+        std::fs::write(
+            project_dir.join("mod.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nnu-a = { path = \"../nu-a\" }\n",
+        )
+        .unwrap();
+
+        let named = |names: &[&str]| UpdateOptions {
+            names: names.iter().map(|n| n.to_string()).collect(),
+            ..Default::default()
+        };
+
+        let err = update(&project_dir, &named(&["nu-a"])).unwrap_err();
+        assert!(err.to_string().contains("run `nuance install`"));
+
+        install(&project_dir, &InstallOptions::default()).unwrap();
+        update(&project_dir, &named(&["nu-a"])).unwrap();
+
+        let err = update(&project_dir, &named(&["nu-missing"])).unwrap_err();
+        assert!(err.to_string().contains("not found in mod.lock"));
+
+        let precise = UpdateOptions {
+            precise: Some("v1.0.0".to_string()),
+            ..named(&["nu-a", "nu-b"])
+        };
+        let err = update(&project_dir, &precise).unwrap_err();
+        assert!(err.to_string().contains("exactly one package"));
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
 }
@@ -0,0 +1,80 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The configured number of parallel jobs; 0 means one per CPU.
+static JOBS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set how many fetches and exports may run at once.
+pub fn set(jobs: usize) {
+    JOBS.store(jobs, Ordering::Relaxed);
+}
+
+/// The number of parallel jobs to use, defaulting to the number of CPUs.
+pub fn count() -> usize {
+    match JOBS.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    }
+}
+
+/// Apply `f` to every item on up to [`count`] threads.
+///
+/// Results come back in the order of `items`, whichever finishes first.
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    map_on(count(), items, f)
+}
+
+/// Apply `f` to every item on up to `workers` threads.
+fn map_on<T, R, F>(workers: usize, items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let workers = workers.min(items.len());
+    if workers <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else { break };
+                    let result = f(item);
+                    results.lock().expect("no worker panicked")[i] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .expect("no worker panicked")
+        .into_iter()
+        .map(|r| r.expect("every item was processed"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_keeps_input_order() {
+        let items: Vec<u64> = (0..32).collect();
+        let results = map_on(4, &items, |n| {
+            // Finish out of order
+            std::thread::sleep(std::time::Duration::from_millis(32 - n));
+            n * 2
+        });
+        assert_eq!(results, items.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+}
@@ -1,28 +1,142 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::Path;
 
-use crate::error::Result;
+use crate::error::{NuanceError, Result};
+use crate::manifest::DependencySpec;
+use crate::version;
+
+/// The lockfile format version written by this build.
+///
+/// Version 2 added dependency edges, the root dependency list, source kinds,
+/// branch names and generator metadata. Version 1 files are still read.
+pub const LOCKFILE_VERSION: u32 = 2;
 
 /// The `mod.lock` lockfile.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Lockfile {
     pub version: u32,
-    #[serde(rename = "package")]
+    /// Direct dependencies of the project (or global config), sorted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+    #[serde(default, rename = "package")]
     pub packages: Vec<LockedPackage>,
 }
 
+/// Information about the tool that wrote a lockfile.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Metadata {
+    pub generator: String,
+}
+
+impl Metadata {
+    /// Metadata naming this build of nuance.
+    pub fn current() -> Self {
+        Self {
+            generator: format!("nuance {}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+/// Where a locked package comes from.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    #[default]
+    Git,
+    Path,
+}
+
 /// A single locked package entry.
+///
+/// Git packages record their URL and commit; path packages record the
+/// directory (relative to the project) and have no commit.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct LockedPackage {
     pub name: String,
-    pub git: String,
+    #[serde(default)]
+    pub source: SourceKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    /// The branch the commit was taken from, for branch dependencies.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tag: Option<String>,
-    pub rev: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
     pub sha256: String,
+    /// Only needed for development of the root project.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub dev: bool,
+    /// Names of the packages this one depends on directly, sorted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// The dependency spec this entry was resolved from. Used to detect
+    /// entries whose spec changed in mod.toml since they were locked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested: Option<DependencySpec>,
+}
+
+impl LockedPackage {
+    /// Whether this entry was locked from `spec`.
+    ///
+    /// Entries written before the requested spec was recorded are compared
+    /// by their source and the ref they are pinned to instead.
+    pub fn locked_from(&self, spec: &DependencySpec) -> bool {
+        if let Some(requested) = &self.requested {
+            return requested == spec;
+        }
+        if spec.path.is_some() || self.path.is_some() {
+            return self.path == spec.path;
+        }
+        if self.git != spec.git || self.subdir != spec.subdir {
+            return false;
+        }
+        if let Some(tag) = &spec.tag {
+            self.tag.as_ref() == Some(tag)
+        } else if let Some(rev) = &spec.rev {
+            self.rev
+                .as_ref()
+                .is_some_and(|r| r.starts_with(rev.as_str()))
+        } else if let Some(branch) = &spec.branch {
+            self.tag.is_none() && self.branch.as_ref().is_none_or(|b| b == branch)
+        } else if let Some(req) = &spec.version {
+            let Ok(req) = version::parse_req(&self.name, req) else {
+                return false;
+            };
+            self.tag
+                .as_deref()
+                .and_then(version::parse_tag)
+                .is_some_and(|v| req.matches(&v))
+        } else {
+            false
+        }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
 }
 
 impl Lockfile {
+    /// Create a lockfile in the current format.
+    pub fn new(mut roots: Vec<String>, packages: Vec<LockedPackage>) -> Self {
+        roots.sort();
+        Self {
+            version: LOCKFILE_VERSION,
+            roots,
+            metadata: Some(Metadata::current()),
+            packages,
+        }
+    }
+
     /// Read a lockfile from disk.
     pub fn from_path(path: &Path) -> Result<Self> {
         let content = std::fs::read_to_string(path)?;
@@ -30,8 +144,56 @@ impl Lockfile {
     }
 
     /// Parse a lockfile from a TOML string.
+    ///
+    /// Version 1 files are filled in with what can be derived (source kinds
+    /// and branch names) but keep `version = 1`, since they carry no
+    /// dependency edges. They are upgraded the next time they are resolved.
     pub fn from_str(s: &str) -> Result<Self> {
-        Ok(toml::from_str(s)?)
+        let mut lockfile: Self = toml::from_str(s)?;
+        match lockfile.version {
+            1 => {
+                for pkg in &mut lockfile.packages {
+                    if pkg.path.is_some() {
+                        pkg.source = SourceKind::Path;
+                    }
+                    if pkg.branch.is_none() {
+                        pkg.branch = pkg.requested.as_ref().and_then(|r| r.branch.clone());
+                    }
+                }
+            }
+            LOCKFILE_VERSION => {}
+            other => {
+                return Err(NuanceError::Lockfile(format!(
+                    "unsupported lockfile version {other} (this nuance supports up to {LOCKFILE_VERSION})"
+                )));
+            }
+        }
+        Ok(lockfile)
+    }
+
+    /// Whether the lockfile records dependency edges, so direct and
+    /// transitive packages can be told apart.
+    pub fn has_edges(&self) -> bool {
+        self.version >= 2
+    }
+
+    /// Names of the locked packages reachable from `roots` through the
+    /// recorded dependency edges.
+    pub fn reachable_from<'a, I>(&'a self, roots: I) -> HashSet<&'a str>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut seen = HashSet::new();
+        let mut queue: Vec<&str> = roots.into_iter().collect();
+        while let Some(name) = queue.pop() {
+            let Some(pkg) = self.find_package(name) else {
+                continue;
+            };
+            if seen.insert(pkg.name.as_str()) {
+                queue.extend(pkg.dependencies.iter().map(String::as_str));
+            }
+        }
+        seen
     }
 
     /// Serialize the lockfile to a TOML string with the header comment.
@@ -60,25 +222,58 @@ mod tests {
     use super::*;
 
     fn sample_lockfile() -> Lockfile {
-        Lockfile {
-            version: 1,
-            packages: vec![
+        Lockfile::new(
+            vec!["nu-git-utils".to_string(), "nu-str-extras".to_string()],
+            vec![
                 LockedPackage {
                     name: "nu-git-utils".to_string(),
-                    git: "https://github.com/someuser/nu-git-utils".to_string(),
+                    source: SourceKind::Git,
+                    git: Some("https://github.com/someuser/nu-git-utils".to_string()),
+                    path: None,
+                    subdir: None,
+                    branch: None,
                     tag: Some("v0.2.0".to_string()),
-                    rev: "d4e8f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8".to_string(),
+                    rev: Some("d4e8f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8".to_string()),
                     sha256: "abc123".to_string(),
+                    dev: false,
+                    dependencies: vec!["nu-common".to_string()],
+                    requested: None,
                 },
                 LockedPackage {
                     name: "nu-str-extras".to_string(),
-                    git: "https://github.com/someuser/nu-str-extras".to_string(),
+                    source: SourceKind::Git,
+                    git: Some("https://github.com/someuser/nu-str-extras".to_string()),
+                    path: None,
+                    subdir: Some("modules/str-extras".to_string()),
+                    branch: None,
                     tag: Some("v1.0.0".to_string()),
-                    rev: "1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b".to_string(),
+                    rev: Some("1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b".to_string()),
                     sha256: "def456".to_string(),
+                    dev: true,
+                    dependencies: Vec::new(),
+                    requested: Some(DependencySpec {
+                        git: Some("https://github.com/someuser/nu-str-extras".to_string()),
+                        tag: Some("v1.0.0".to_string()),
+                        subdir: Some("modules/str-extras".to_string()),
+                        ..Default::default()
+                    }),
+                },
+                LockedPackage {
+                    name: "nu-common".to_string(),
+                    source: SourceKind::Path,
+                    git: None,
+                    path: Some("../nu-common".to_string()),
+                    subdir: None,
+                    branch: None,
+                    tag: None,
+                    rev: None,
+                    sha256: "789abc".to_string(),
+                    dev: false,
+                    dependencies: Vec::new(),
+                    requested: None,
                 },
             ],
-        }
+        )
     }
 
     #[test]
@@ -95,7 +290,10 @@ mod tests {
     fn find_package_by_name() {
         let lock = sample_lockfile();
         let pkg = lock.find_package("nu-git-utils").unwrap();
-        assert_eq!(pkg.rev, "d4e8f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8");
+        assert_eq!(
+            pkg.rev.as_deref(),
+            Some("d4e8f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8")
+        );
         assert!(lock.find_package("nonexistent").is_none());
     }
 
@@ -116,5 +314,59 @@ sha256 = "abc123"
         assert_eq!(lock.version, 1);
         assert_eq!(lock.packages.len(), 1);
         assert_eq!(lock.packages[0].name, "nu-git-utils");
+        assert_eq!(lock.packages[0].source, SourceKind::Git);
+        assert!(!lock.packages[0].dev);
+        assert!(!lock.has_edges());
+    }
+
+    #[test]
+    fn upgrades_v1_path_and_branch_entries() {
+        let toml = r#"
+version = 1
+
+[[package]]
+name = "nu-common"
+path = "../nu-common"
+sha256 = "789abc"
+
+[[package]]
+name = "nu-nightly"
+git = "https://github.com/someuser/nu-nightly"
+rev = "d4e8f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8"
+sha256 = "abc123"
+
+[package.requested]
+git = "https://github.com/someuser/nu-nightly"
+branch = "main"
+"#;
+        let lock = Lockfile::from_str(toml).unwrap();
+        assert_eq!(lock.packages[0].source, SourceKind::Path);
+        assert_eq!(lock.packages[1].branch.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn writes_v2_with_roots_edges_and_metadata() {
+        let serialized = sample_lockfile().to_toml_string().unwrap();
+        assert!(serialized.contains("version = 2"));
+        assert!(serialized.contains("[metadata]\ngenerator = \"nuance "));
+        assert!(serialized.contains("source = \"path\""));
+
+        let parsed = Lockfile::from_str(&serialized).unwrap();
+        assert!(parsed.has_edges());
+        assert_eq!(parsed.roots, ["nu-git-utils", "nu-str-extras"]);
+        assert_eq!(parsed.packages[0].dependencies, ["nu-common"]);
+    }
+
+    #[test]
+    fn reachable_follows_dependency_edges() {
+        let lock = sample_lockfile();
+        let reachable = lock.reachable_from(["nu-git-utils"]);
+        assert_eq!(reachable, HashSet::from(["nu-git-utils", "nu-common"]));
+    }
+
+    #[test]
+    fn rejects_newer_lockfile_versions() {
+        let err = Lockfile::from_str("version = 3\n").unwrap_err();
+        assert!(err.to_string().contains("unsupported lockfile version 3"));
     }
 }
@@ -1,30 +1,72 @@
+mod auth;
+mod cache;
 mod checksum;
 mod cli;
 mod config;
+mod document;
 mod error;
 mod git;
+mod graph;
 mod installer;
+mod jobs;
 mod lockfile;
 mod manifest;
+mod outdated;
+mod progress;
 mod resolver;
+#[cfg(test)]
+mod test_support;
+mod upgrade;
+mod version;
 
 use std::path::Path;
+use std::process::ExitCode;
 
-use cli::Commands;
+use cli::{CacheCommand, Commands};
 use config::GlobalConfig;
+use document::Document;
 use error::Result;
+use graph::Graph;
+use installer::{InstallOptions, UpdateOptions};
 use manifest::{DependencySpec, Manifest, Package};
 
-fn main() {
+fn main() -> ExitCode {
     let cli = cli::parse();
 
-    if let Err(e) = run(cli.command) {
-        eprintln!("error: {e}");
-        std::process::exit(1);
+    match configure(&cli).and_then(|()| run(cli.command)) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Apply settings shared by all commands, preferring command-line flags
+/// over the global config.
+fn configure(cli: &cli::Cli) -> Result<()> {
+    let config = GlobalConfig::load_or_default()?;
+    if let Some(jobs) = cli.jobs.or(config.jobs) {
+        jobs::set(jobs);
     }
+    git::set_offline(cli.offline || env_flag("NUANCE_OFFLINE") || config.offline);
+    auth::configure(auth::Settings {
+        ssh_key: config.ssh_key_path(),
+        host_tokens: config.provider_tokens(),
+        token_hosts: config.provider_hosts(),
+    });
+    cache::set_max_size(config.cache_max_size.clone());
+    Ok(())
+}
+
+/// Whether an environment variable is set to something other than empty,
+/// `0` or `false`.
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|v| !matches!(v.trim(), "" | "0" | "false"))
 }
 
-fn run(command: Commands) -> Result<()> {
+/// Run a command and return the status to exit with.
+fn run(command: Commands) -> Result<ExitCode> {
     let cwd = std::env::current_dir()?;
 
     match command {
@@ -33,25 +75,102 @@ fn run(command: Commands) -> Result<()> {
             version,
             description,
         } => cmd_init(&cwd, name, version, description),
-        Commands::Install { global, frozen } => {
+        Commands::Install {
+            global,
+            frozen,
+            no_dev,
+        } => {
+            let options = InstallOptions { frozen, no_dev };
+            if global {
+                cmd_install_global(&options)
+            } else {
+                cmd_install(&cwd, &options)
+            }
+        }
+        Commands::Update {
+            global,
+            names,
+            recursive,
+            precise,
+        } => {
+            let options = UpdateOptions {
+                names,
+                recursive,
+                precise,
+            };
             if global {
-                cmd_install_global(frozen)
+                cmd_update_global(&options)
             } else {
-                cmd_install(&cwd, frozen)
+                cmd_update(&cwd, &options)
             }
         }
-        Commands::Update => cmd_update(&cwd),
+        Commands::Outdated {
+            global,
+            json,
+            exit_code,
+        } => {
+            let lock_path = if global {
+                config::global_lock_path()?
+            } else {
+                cwd.join("mod.lock")
+            };
+            return cmd_outdated(&lock_path, json, exit_code);
+        }
+        Commands::Upgrade {
+            global,
+            names,
+            compatible,
+            dry_run,
+        } => {
+            if global {
+                cmd_upgrade_global(&names, compatible, dry_run)
+            } else {
+                cmd_upgrade(&cwd, &names, compatible, dry_run)
+            }
+        }
+        Commands::Tree {
+            global,
+            package,
+            depth,
+            invert,
+            format,
+        } => {
+            let graph = if global {
+                global_graph()?
+            } else {
+                project_graph(&cwd)?
+            };
+            cmd_tree(graph, package, depth, invert, format)
+        }
+        Commands::Why { global, name } => {
+            let graph = if global {
+                global_graph()?
+            } else {
+                project_graph(&cwd)?
+            };
+            cmd_why(&graph, &name)
+        }
         Commands::Add {
             global,
+            dev,
             url,
             tag,
             rev,
             branch,
+            version_req,
+            pre,
         } => {
+            let refs = RefArgs {
+                tag,
+                rev,
+                branch,
+                version: version_req,
+                pre,
+            };
             if global {
-                cmd_add_global(url, tag, rev, branch)
+                cmd_add_global(url, refs)
             } else {
-                cmd_add(&cwd, url, tag, rev, branch)
+                cmd_add(&cwd, url, refs, dev)
             }
         }
         Commands::Remove { global, name } => {
@@ -61,8 +180,10 @@ fn run(command: Commands) -> Result<()> {
                 cmd_remove(&cwd, name)
             }
         }
+        Commands::Cache { command } => cmd_cache(command),
         Commands::Hook => cmd_hook(),
-    }
+    }?;
+    Ok(ExitCode::SUCCESS)
 }
 
 fn cmd_init(
@@ -96,6 +217,7 @@ fn cmd_init(
             nu_version: None,
         },
         dependencies: Default::default(),
+        dev_dependencies: Default::default(),
     };
 
     let content = manifest.to_toml_string()?;
@@ -115,34 +237,202 @@ fn cmd_init(
     Ok(())
 }
 
-fn cmd_install(dir: &Path, frozen: bool) -> Result<()> {
-    installer::install(dir, frozen)
+fn cmd_install(dir: &Path, options: &InstallOptions) -> Result<()> {
+    installer::install(dir, options)
 }
 
-fn cmd_install_global(frozen: bool) -> Result<()> {
-    installer::install_global(frozen)
+fn cmd_install_global(options: &InstallOptions) -> Result<()> {
+    installer::install_global(options)
 }
 
-fn cmd_update(dir: &Path) -> Result<()> {
-    installer::update(dir)
+fn cmd_update(dir: &Path, options: &UpdateOptions) -> Result<()> {
+    installer::update(dir, options)
 }
 
-fn cmd_add(
-    dir: &Path,
-    url: String,
-    tag: Option<String>,
-    rev: Option<String>,
-    branch: Option<String>,
-) -> Result<()> {
-    // Load existing manifest (or error if none)
-    let mut manifest = Manifest::from_dir(dir)?;
-    let provider_base = if is_git_url(url.trim()) {
-        None
+fn cmd_update_global(options: &UpdateOptions) -> Result<()> {
+    installer::update_global(options)
+}
+
+fn cmd_outdated(lock_path: &Path, json: bool, exit_code: bool) -> Result<ExitCode> {
+    let outdated = outdated::check(lock_path)?;
+
+    if json {
+        outdated::print_json(&outdated)?;
+    } else if outdated.is_empty() {
+        eprintln!("All dependencies are up to date.");
     } else {
-        let config = GlobalConfig::load_or_default()?;
-        Some(config.default_git_provider_base_url()?)
+        outdated::print_table(&outdated);
+    }
+
+    // With --exit-code, fail so CI can flag stale dependencies
+    if exit_code && !outdated.is_empty() {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn cmd_upgrade(dir: &Path, names: &[String], compatible: bool, dry_run: bool) -> Result<()> {
+    let manifest = Manifest::from_dir(dir)?;
+    let roots = manifest
+        .dependencies
+        .iter()
+        .chain(manifest.dev_dependencies.iter());
+    let plan = upgrade::plan(roots, names, compatible)?;
+    print_upgrades(&plan.upgrades);
+    if dry_run {
+        eprintln!("Dry run: mod.toml was not changed.");
+        return Ok(());
+    }
+
+    if !plan.upgrades.is_empty() {
+        let mod_toml = dir.join("mod.toml");
+        let mut doc = Document::from_path(&mod_toml)?;
+        for upgrade in &plan.upgrades {
+            let section = if manifest.dev_dependencies.contains_key(&upgrade.name) {
+                "dev-dependencies"
+            } else {
+                "dependencies"
+            };
+            doc.set_dependency(section, &upgrade.name, &upgrade.spec)?;
+        }
+        doc.write_to(&mod_toml)?;
+    }
+
+    match relock_options(&dir.join("mod.lock"), plan.relock)? {
+        Some(options) => installer::update(dir, &options),
+        None => installer::install(dir, &InstallOptions::default()),
+    }
+}
+
+fn cmd_upgrade_global(names: &[String], compatible: bool, dry_run: bool) -> Result<()> {
+    let mut config = GlobalConfig::load()?;
+    let plan = upgrade::plan(&config.dependencies, names, compatible)?;
+    print_upgrades(&plan.upgrades);
+    if dry_run {
+        eprintln!("Dry run: global config was not changed.");
+        return Ok(());
+    }
+
+    if !plan.upgrades.is_empty() {
+        for upgrade in &plan.upgrades {
+            config
+                .dependencies
+                .insert(upgrade.name.clone(), upgrade.spec.clone());
+        }
+        config.save()?;
+    }
+
+    match relock_options(&config::global_lock_path()?, plan.relock)? {
+        Some(options) => installer::update_global(&options),
+        None => installer::install_global(&InstallOptions::default()),
+    }
+}
+
+fn print_upgrades(upgrades: &[upgrade::Upgrade]) {
+    if upgrades.is_empty() {
+        eprintln!("No requirements to upgrade.");
+    }
+    for upgrade in upgrades {
+        eprintln!("  {}: {} -> {}", upgrade.name, upgrade.from, upgrade.to);
+    }
+}
+
+/// Re-resolve only the upgraded packages that are already locked. Returns
+/// `None` when a plain install is enough: anything not yet in the lockfile,
+/// or whose spec was just rewritten, is resolved by install anyway.
+fn relock_options(lock_path: &Path, names: Vec<String>) -> Result<Option<UpdateOptions>> {
+    if !lock_path.exists() {
+        return Ok(None);
+    }
+    let lockfile = lockfile::Lockfile::from_path(lock_path)?;
+    let names: Vec<String> = names
+        .into_iter()
+        .filter(|name| lockfile.find_package(name).is_some())
+        .collect();
+    if names.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(UpdateOptions {
+        names,
+        ..Default::default()
+    }))
+}
+
+/// The project's dependency graph, read from the lockfile's edges when it
+/// has them and resolved otherwise.
+fn project_graph(dir: &Path) -> Result<Graph> {
+    let manifest = Manifest::from_dir(dir)?;
+    let lock_path = dir.join("mod.lock");
+    let locked = if lock_path.exists() {
+        let lockfile = lockfile::Lockfile::from_path(&lock_path)?;
+        if let Some(graph) = Graph::from_lockfile(&manifest.package.name, &lockfile) {
+            return Ok(graph);
+        }
+        lockfile.packages
+    } else {
+        Vec::new()
+    };
+    let resolved = resolver::resolve(dir, &locked, &resolver::Unlock::default())?;
+    let roots: Vec<String> = manifest
+        .dependencies
+        .keys()
+        .chain(manifest.dev_dependencies.keys())
+        .cloned()
+        .collect();
+    Ok(Graph::new(&manifest.package.name, &roots, &resolved))
+}
+
+/// The global dependency graph, read from the global lockfile's edges when
+/// it has them and resolved otherwise.
+fn global_graph() -> Result<Graph> {
+    let config = GlobalConfig::load()?;
+    let lock_path = config::global_lock_path()?;
+    let locked = if lock_path.exists() {
+        let lockfile = lockfile::Lockfile::from_path(&lock_path)?;
+        if let Some(graph) = Graph::from_lockfile("global", &lockfile) {
+            return Ok(graph);
+        }
+        lockfile.packages
+    } else {
+        Vec::new()
     };
-    let url = normalize_dependency_source(&url, provider_base.as_deref())?;
+    let resolved = resolver::resolve_from_deps(
+        &config.dependencies,
+        &config::global_config_dir()?,
+        &locked,
+        &resolver::Unlock::default(),
+    )?;
+    let roots: Vec<String> = config.dependencies.keys().cloned().collect();
+    Ok(Graph::new("global", &roots, &resolved))
+}
+
+fn cmd_tree(
+    graph: Graph,
+    package: Option<String>,
+    depth: Option<usize>,
+    invert: bool,
+    format: graph::Format,
+) -> Result<()> {
+    let mut graph = if invert { graph.inverted() } else { graph };
+    if let Some(name) = package {
+        graph = graph.focus(&name)?;
+    }
+    print!("{}", graph.render(format, depth)?);
+    Ok(())
+}
+
+fn cmd_why(graph: &Graph, name: &str) -> Result<()> {
+    for path in graph.paths_to(name)? {
+        println!("{}", path.join(" -> "));
+    }
+    Ok(())
+}
+
+fn cmd_add(dir: &Path, url: String, refs: RefArgs, dev: bool) -> Result<()> {
+    // Load existing manifest (or error if none)
+    let manifest = Manifest::from_dir(dir)?;
+    let config = GlobalConfig::load_or_default()?;
+    let url = normalize_dependency_source(&url, Some(&config))?;
 
     // Derive package name from URL
     let pkg_name = git::repo_name_from_url(&url).ok_or_else(|| {
@@ -150,41 +440,39 @@ fn cmd_add(
     })?;
 
     // Check if already added
-    if manifest.dependencies.contains_key(&pkg_name) {
+    if manifest.dependencies.contains_key(&pkg_name)
+        || manifest.dev_dependencies.contains_key(&pkg_name)
+    {
         return Err(error::NuanceError::Manifest(format!(
             "dependency '{pkg_name}' already exists in mod.toml"
         )));
     }
 
     // If no ref spec given, auto-detect: try latest tag, fall back to default branch
-    let dep_spec = auto_detect_dep_spec(&url, tag, rev, branch)?;
+    let dep_spec = auto_detect_dep_spec(&url, refs)?;
 
     dep_spec.validate(&pkg_name)?;
 
-    // Add to manifest and write back
-    manifest.dependencies.insert(pkg_name.clone(), dep_spec);
-    let content = manifest.to_toml_string()?;
-    std::fs::write(dir.join("mod.toml"), content)?;
+    // Add to manifest, editing it in place to keep comments and formatting
+    let section = if dev {
+        "dev-dependencies"
+    } else {
+        "dependencies"
+    };
+    let mod_toml = dir.join("mod.toml");
+    let mut doc = Document::from_path(&mod_toml)?;
+    doc.set_dependency(section, &pkg_name, &dep_spec)?;
+    doc.write_to(&mod_toml)?;
 
-    eprintln!("Added '{pkg_name}' to mod.toml");
+    eprintln!("Added '{pkg_name}' to [{section}] in mod.toml");
 
     // Run install
-    installer::install(dir, false)
+    installer::install(dir, &InstallOptions::default())
 }
 
-fn cmd_add_global(
-    url: String,
-    tag: Option<String>,
-    rev: Option<String>,
-    branch: Option<String>,
-) -> Result<()> {
+fn cmd_add_global(url: String, refs: RefArgs) -> Result<()> {
     let mut config = GlobalConfig::load()?;
-    let provider_base = if is_git_url(url.trim()) {
-        None
-    } else {
-        Some(config.default_git_provider_base_url()?)
-    };
-    let url = normalize_dependency_source(&url, provider_base.as_deref())?;
+    let url = normalize_dependency_source(&url, Some(&config))?;
 
     // Derive package name from URL
     let pkg_name = git::repo_name_from_url(&url).ok_or_else(|| {
@@ -198,7 +486,7 @@ fn cmd_add_global(
         )));
     }
 
-    let dep_spec = auto_detect_dep_spec(&url, tag, rev, branch)?;
+    let dep_spec = auto_detect_dep_spec(&url, refs)?;
 
     dep_spec.validate(&pkg_name)?;
 
@@ -209,23 +497,26 @@ fn cmd_add_global(
     eprintln!("Added '{pkg_name}' to global config");
 
     // Run global install
-    installer::install_global(false)
+    installer::install_global(&InstallOptions::default())
 }
 
 fn cmd_remove(dir: &Path, name: String) -> Result<()> {
-    // Load existing manifest
-    let mut manifest = Manifest::from_dir(dir)?;
+    // Load existing manifest (validates it before editing)
+    Manifest::from_dir(dir)?;
+    let mod_toml = dir.join("mod.toml");
+    let mut doc = Document::from_path(&mod_toml)?;
 
     // Check the dep exists
-    if manifest.dependencies.remove(&name).is_none() {
+    if !doc.remove_dependency("dependencies", &name)
+        && !doc.remove_dependency("dev-dependencies", &name)
+    {
         return Err(error::NuanceError::Manifest(format!(
             "dependency '{name}' not found in mod.toml"
         )));
     }
 
     // Write updated manifest
-    let content = manifest.to_toml_string()?;
-    std::fs::write(dir.join("mod.toml"), content)?;
+    doc.write_to(&mod_toml)?;
     eprintln!("Removed '{name}' from mod.toml");
 
     // Remove from .nu_modules/
@@ -246,7 +537,7 @@ fn cmd_remove(dir: &Path, name: String) -> Result<()> {
 
     // Regenerate activate.nu from the updated manifest and lockfile state.
     eprintln!("Regenerating activate.nu...");
-    installer::install(dir, false)?;
+    installer::install(dir, &InstallOptions::default())?;
 
     Ok(())
 }
@@ -284,11 +575,77 @@ fn cmd_remove_global(name: String) -> Result<()> {
 
     // Regenerate the activate.nu overlay with remaining global packages
     eprintln!("Regenerating global activate.nu...");
-    installer::install_global(false)?;
+    installer::install_global(&InstallOptions::default())?;
 
     Ok(())
 }
 
+fn cmd_cache(command: CacheCommand) -> Result<()> {
+    match command {
+        CacheCommand::List => {
+            let entries = cache::entries()?;
+            if entries.is_empty() {
+                eprintln!("The git cache is empty.");
+                return Ok(());
+            }
+            let width = entries
+                .iter()
+                .map(|e| cache_label(e).len())
+                .max()
+                .unwrap_or(0);
+            for entry in &entries {
+                println!(
+                    "{:<width$}  {:>10}  {}",
+                    cache_label(entry),
+                    cache::format_size(entry.size),
+                    cache::format_age(entry.last_used)
+                );
+            }
+            let total: u64 = entries.iter().map(|e| e.size).sum();
+            eprintln!(
+                "\n{} cached repo{}, {} total",
+                entries.len(),
+                if entries.len() == 1 { "" } else { "s" },
+                cache::format_size(total)
+            );
+        }
+        CacheCommand::Clean { unused } => {
+            let removed = cache::clean(unused)?;
+            for entry in &removed {
+                eprintln!("  Removed {}", cache_label(entry));
+            }
+            let freed: u64 = removed.iter().map(|e| e.size).sum();
+            eprintln!(
+                "Removed {} cached repo{}, freeing {}",
+                removed.len(),
+                if removed.len() == 1 { "" } else { "s" },
+                cache::format_size(freed)
+            );
+        }
+        CacheCommand::Gc => {
+            let collected = cache::gc()?;
+            let before: u64 = collected.iter().map(|(e, _)| e.size).sum();
+            let after: u64 = collected.iter().map(|(_, size)| size).sum();
+            eprintln!(
+                "Collected {} cached repo{}: {} -> {}",
+                collected.len(),
+                if collected.len() == 1 { "" } else { "s" },
+                cache::format_size(before),
+                cache::format_size(after)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// How a cache entry is shown: its URL, or its directory if unreadable.
+fn cache_label(entry: &cache::Entry) -> String {
+    entry
+        .url
+        .clone()
+        .unwrap_or_else(|| entry.path.display().to_string())
+}
+
 fn cmd_hook() -> Result<()> {
     let hook_script = r#"# nuance auto-activate hook — add this to your config.nu (or env.nu)
 $env.config.hooks.env_change.PWD = (
@@ -311,7 +668,9 @@ $env.config.hooks.env_change.PWD = (
     Ok(())
 }
 
-fn normalize_dependency_source(input: &str, provider_base_url: Option<&str>) -> Result<String> {
+/// Turn a git URL, `owner/repo` or `<provider>:owner/repo` shorthand into
+/// a clone URL. Shorthand is expanded through the providers in `config`.
+fn normalize_dependency_source(input: &str, config: Option<&GlobalConfig>) -> Result<String> {
     let trimmed = input.trim();
 
     if trimmed.is_empty() {
@@ -324,13 +683,21 @@ fn normalize_dependency_source(input: &str, provider_base_url: Option<&str>) ->
         return Ok(trimmed.to_string());
     }
 
-    if is_repo_shorthand(trimmed) {
-        let provider_base = provider_base_url.ok_or_else(|| {
+    let (alias, path) = match trimmed.split_once(':') {
+        Some((alias, path)) if is_provider_alias(alias) => (Some(alias), path),
+        _ => (None, trimmed),
+    };
+    if is_repo_shorthand(path) {
+        let config = config.ok_or_else(|| {
             error::NuanceError::Other(
                 "a default git provider is required for owner/repo shorthand".to_string(),
             )
         })?;
-        return Ok(format!("{provider_base}/{trimmed}"));
+        let provider = match alias {
+            Some(alias) => config.provider(alias)?,
+            None => config.default_provider()?,
+        };
+        return provider.repo_url(path);
     }
 
     Err(error::NuanceError::Other(format!(
@@ -338,6 +705,13 @@ fn normalize_dependency_source(input: &str, provider_base_url: Option<&str>) ->
     )))
 }
 
+fn is_provider_alias(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
 fn is_git_url(value: &str) -> bool {
     value.contains("://") || value.starts_with("git@")
 }
@@ -354,44 +728,57 @@ fn is_repo_shorthand(value: &str) -> bool {
         && !repo.chars().any(char::is_whitespace)
 }
 
-/// Auto-detect the dependency spec from a URL, optionally with an explicit ref.
-///
-/// If no tag/rev/branch is given, tries the latest tag first, then falls back
-/// to the default branch.
-fn auto_detect_dep_spec(
-    url: &str,
+/// The ref options given to `nuance add`.
+struct RefArgs {
     tag: Option<String>,
     rev: Option<String>,
     branch: Option<String>,
-) -> Result<DependencySpec> {
-    if tag.is_none() && rev.is_none() && branch.is_none() {
+    version: Option<String>,
+    /// Consider pre-release tags during auto-detection.
+    pre: bool,
+}
+
+/// Auto-detect the dependency spec from a URL, optionally with an explicit ref.
+///
+/// If no tag/rev/branch/version is given, tries the latest semver tag first,
+/// then falls back to the remote's default branch.
+fn auto_detect_dep_spec(url: &str, refs: RefArgs) -> Result<DependencySpec> {
+    let RefArgs {
+        tag,
+        rev,
+        branch,
+        version,
+        pre,
+    } = refs;
+
+    if tag.is_none() && rev.is_none() && branch.is_none() && version.is_none() {
         eprintln!("Fetching {url} to detect version...");
         let repo_path = git::clone_or_fetch(url)?;
 
-        if let Some(latest) = git::latest_tag(&repo_path)? {
+        if let Some(latest) = git::latest_tag(&repo_path, pre)? {
             eprintln!("  Found latest tag: {latest}");
             Ok(DependencySpec {
-                git: url.to_string(),
+                git: Some(url.to_string()),
                 tag: Some(latest),
-                rev: None,
-                branch: None,
+                ..Default::default()
             })
         } else {
             let default_br = git::default_branch(&repo_path)?;
             eprintln!("  No tags found, using branch: {default_br}");
             Ok(DependencySpec {
-                git: url.to_string(),
-                tag: None,
-                rev: None,
+                git: Some(url.to_string()),
                 branch: Some(default_br),
+                ..Default::default()
             })
         }
     } else {
         Ok(DependencySpec {
-            git: url.to_string(),
+            git: Some(url.to_string()),
+            version,
             tag,
             rev,
             branch,
+            ..Default::default()
         })
     }
 }
@@ -406,6 +793,7 @@ mod tests {
             modules_dir: None,
             default_git_provider: provider.to_string(),
             dependencies: HashMap::new(),
+            ..Default::default()
         }
     }
 
@@ -421,21 +809,43 @@ mod tests {
     #[test]
     fn normalize_dependency_source_expands_repo_shorthand() {
         let config = config_with_provider("github");
-        let provider = config.default_git_provider_base_url().unwrap();
         let expanded =
-            normalize_dependency_source("freepicheep/nu-salesforce", Some(provider.as_str()))
-                .unwrap();
+            normalize_dependency_source("freepicheep/nu-salesforce", Some(&config)).unwrap();
         assert_eq!(expanded, "https://github.com/freepicheep/nu-salesforce");
     }
 
     #[test]
     fn normalize_dependency_source_uses_custom_provider() {
         let config = config_with_provider("gitlab");
-        let provider = config.default_git_provider_base_url().unwrap();
-        let expanded = normalize_dependency_source("group/repo", Some(provider.as_str())).unwrap();
+        let expanded = normalize_dependency_source("group/repo", Some(&config)).unwrap();
         assert_eq!(expanded, "https://gitlab.com/group/repo");
     }
 
+    #[test]
+    fn normalize_dependency_source_expands_named_providers() {
+        let mut config = config_with_provider("github");
+        config.providers.insert(
+            "work".to_string(),
+            config::Provider {
+                base_url: "https://gitlab.example.com".to_string(),
+                token_env: Some("WORK_TOKEN".to_string()),
+                protocol: config::Protocol::Ssh,
+            },
+        );
+
+        let expanded = normalize_dependency_source("work:team/repo", Some(&config)).unwrap();
+        assert_eq!(expanded, "git@gitlab.example.com:team/repo.git");
+        let expanded = normalize_dependency_source("codeberg:user/repo", Some(&config)).unwrap();
+        assert_eq!(expanded, "https://codeberg.org/user/repo");
+
+        let err = normalize_dependency_source("nowhere:team/repo", Some(&config)).unwrap_err();
+        assert!(err.to_string().contains("[providers.nowhere]"));
+
+        config.default_git_provider = "work".to_string();
+        let expanded = normalize_dependency_source("team/repo", Some(&config)).unwrap();
+        assert_eq!(expanded, "git@gitlab.example.com:team/repo.git");
+    }
+
     #[test]
     fn normalize_dependency_source_rejects_invalid_input() {
         let err = normalize_dependency_source("just-a-repo", None).unwrap_err();
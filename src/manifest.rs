@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Component, Path};
 
 use crate::error::{NuanceError, Result};
+use crate::version;
 
 /// The top-level `mod.toml` manifest.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -10,6 +11,14 @@ pub struct Manifest {
     pub package: Package,
     #[serde(default)]
     pub dependencies: HashMap<String, DependencySpec>,
+    /// Dependencies only needed when developing this module (tests, fixtures).
+    /// They are installed for the root project only, never transitively.
+    #[serde(
+        default,
+        rename = "dev-dependencies",
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub dev_dependencies: HashMap<String, DependencySpec>,
 }
 
 /// The `[package]` section of a manifest.
@@ -28,9 +37,19 @@ pub struct Package {
 }
 
 /// A single dependency specification from `[dependencies]`.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct DependencySpec {
-    pub git: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    /// A local directory, relative to the manifest that declares it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// A subdirectory of the git repository that holds the module.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subdir: Option<String>,
+    /// A semver requirement matched against the repository's tags.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tag: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -40,33 +59,107 @@ pub struct DependencySpec {
 }
 
 impl DependencySpec {
-    /// Validate that exactly one of tag/rev/branch is specified.
+    /// Validate the dependency source.
+    ///
+    /// Git dependencies must specify exactly one of tag/rev/branch/version;
+    /// path dependencies must not specify any of them.
     pub fn validate(&self, name: &str) -> Result<()> {
-        let count = [&self.tag, &self.rev, &self.branch]
+        if self.path.is_some() {
+            if self.git.is_some() {
+                return Err(NuanceError::Manifest(format!(
+                    "dependency '{name}': 'path' cannot be combined with 'git'"
+                )));
+            }
+            if let Some(key) = self.ref_key() {
+                return Err(NuanceError::Manifest(format!(
+                    "dependency '{name}': 'path' cannot be combined with '{key}'"
+                )));
+            }
+            if self.subdir.is_some() {
+                return Err(NuanceError::Manifest(format!(
+                    "dependency '{name}': 'path' cannot be combined with 'subdir'"
+                )));
+            }
+            return Ok(());
+        }
+
+        if let Some(ref subdir) = self.subdir {
+            let escapes = Path::new(subdir)
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+            if subdir.trim().is_empty() || escapes {
+                return Err(NuanceError::Manifest(format!(
+                    "dependency '{name}': 'subdir' must be a relative path inside the repository"
+                )));
+            }
+        }
+
+        if self.git.is_none() {
+            return Err(NuanceError::Manifest(format!(
+                "dependency '{name}': must specify either 'git' or 'path'"
+            )));
+        }
+
+        let count = [&self.tag, &self.rev, &self.branch, &self.version]
             .iter()
             .filter(|v| v.is_some())
             .count();
 
         if count == 0 {
             return Err(NuanceError::Manifest(format!(
-                "dependency '{name}': must specify one of 'tag', 'rev', or 'branch'"
+                "dependency '{name}': must specify one of 'tag', 'rev', 'branch', or 'version'"
             )));
         }
         if count > 1 {
             return Err(NuanceError::Manifest(format!(
-                "dependency '{name}': specify only one of 'tag', 'rev', or 'branch'"
+                "dependency '{name}': specify only one of 'tag', 'rev', 'branch', or 'version'"
             )));
         }
+        if let Some(ref req) = self.version {
+            version::parse_req(name, req)?;
+        }
         Ok(())
     }
 
-    /// Returns the git ref string (tag, rev, or branch value).
+    /// All keys of the spec in their canonical order, with their values.
+    pub fn entries(&self) -> [(&'static str, Option<&str>); 7] {
+        [
+            ("git", self.git.as_deref()),
+            ("path", self.path.as_deref()),
+            ("subdir", self.subdir.as_deref()),
+            ("version", self.version.as_deref()),
+            ("tag", self.tag.as_deref()),
+            ("rev", self.rev.as_deref()),
+            ("branch", self.branch.as_deref()),
+        ]
+    }
+
+    /// Returns the name of the ref key that is set, if any.
+    fn ref_key(&self) -> Option<&'static str> {
+        if self.tag.is_some() {
+            Some("tag")
+        } else if self.rev.is_some() {
+            Some("rev")
+        } else if self.branch.is_some() {
+            Some("branch")
+        } else if self.version.is_some() {
+            Some("version")
+        } else {
+            None
+        }
+    }
+
+    /// Returns the git ref string (tag, rev, or branch value), or the
+    /// version requirement for `version` dependencies.
+    ///
+    /// Only valid for git dependencies.
     pub fn ref_spec(&self) -> &str {
         self.rev
             .as_deref()
             .or(self.tag.as_deref())
             .or(self.branch.as_deref())
-            .expect("validated: one of tag/rev/branch is set")
+            .or(self.version.as_deref())
+            .expect("validated: one of tag/rev/branch/version is set")
     }
 }
 
@@ -103,6 +196,14 @@ impl Manifest {
         for (name, spec) in &self.dependencies {
             spec.validate(name)?;
         }
+        for (name, spec) in &self.dev_dependencies {
+            if self.dependencies.contains_key(name) {
+                return Err(NuanceError::Manifest(format!(
+                    "dependency '{name}' is declared in both [dependencies] and [dev-dependencies]"
+                )));
+            }
+            spec.validate(name)?;
+        }
         Ok(())
     }
 
@@ -179,6 +280,160 @@ broken = { git = "https://github.com/user/broken", tag = "v1", branch = "main" }
         assert!(err.to_string().contains("specify only one of"));
     }
 
+    #[test]
+    fn parse_version_requirement() {
+        let toml = r#"
+[package]
+name = "ranged"
+version = "0.1.0"
+
+[dependencies]
+nu-http = { git = "https://github.com/user/nu-http", version = ">=0.4, <0.6" }
+"#;
+        let manifest = Manifest::from_str(toml).unwrap();
+        let spec = &manifest.dependencies["nu-http"];
+        assert_eq!(spec.version.as_deref(), Some(">=0.4, <0.6"));
+        assert_eq!(spec.ref_spec(), ">=0.4, <0.6");
+    }
+
+    #[test]
+    fn reject_invalid_version_requirement() {
+        let toml = r#"
+[package]
+name = "bad"
+version = "0.1.0"
+
+[dependencies]
+broken = { git = "https://github.com/user/broken", version = "one point two" }
+"#;
+        let err = Manifest::from_str(toml).unwrap_err();
+        assert!(err.to_string().contains("invalid version requirement"));
+    }
+
+    #[test]
+    fn parse_path_dependency() {
+        let toml = r#"
+[package]
+name = "local"
+version = "0.1.0"
+
+[dependencies]
+nu-common = { path = "../nu-common" }
+"#;
+        let manifest = Manifest::from_str(toml).unwrap();
+        let spec = &manifest.dependencies["nu-common"];
+        assert_eq!(spec.path.as_deref(), Some("../nu-common"));
+        assert!(spec.git.is_none());
+    }
+
+    #[test]
+    fn reject_path_with_git_or_ref() {
+        let toml = r#"
+[package]
+name = "bad"
+version = "0.1.0"
+
+[dependencies]
+broken = { path = "../broken", git = "https://github.com/user/broken" }
+"#;
+        let err = Manifest::from_str(toml).unwrap_err();
+        assert!(err.to_string().contains("cannot be combined with 'git'"));
+
+        let toml = r#"
+[package]
+name = "bad"
+version = "0.1.0"
+
+[dependencies]
+broken = { path = "../broken", tag = "v1.0.0" }
+"#;
+        let err = Manifest::from_str(toml).unwrap_err();
+        assert!(err.to_string().contains("cannot be combined with 'tag'"));
+    }
+
+    #[test]
+    fn parse_subdir_dependency() {
+        let toml = r#"
+[package]
+name = "mono"
+version = "0.1.0"
+
+[dependencies]
+foo = { git = "https://github.com/user/monorepo", tag = "v1.0.0", subdir = "modules/foo" }
+"#;
+        let manifest = Manifest::from_str(toml).unwrap();
+        assert_eq!(
+            manifest.dependencies["foo"].subdir.as_deref(),
+            Some("modules/foo")
+        );
+    }
+
+    #[test]
+    fn reject_subdir_outside_repo() {
+        let toml = r#"
+[package]
+name = "bad"
+version = "0.1.0"
+
+[dependencies]
+broken = { git = "https://github.com/user/broken", tag = "v1", subdir = "../other" }
+"#;
+        let err = Manifest::from_str(toml).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("relative path inside the repository")
+        );
+    }
+
+    #[test]
+    fn reject_missing_source() {
+        let toml = r#"
+[package]
+name = "bad"
+version = "0.1.0"
+
+[dependencies]
+broken = { tag = "v1.0.0" }
+"#;
+        let err = Manifest::from_str(toml).unwrap_err();
+        assert!(err.to_string().contains("either 'git' or 'path'"));
+    }
+
+    #[test]
+    fn parse_dev_dependencies() {
+        let toml = r#"
+[package]
+name = "tested"
+version = "0.1.0"
+
+[dependencies]
+nu-utils = { git = "https://github.com/user/nu-utils", tag = "v1.0.0" }
+
+[dev-dependencies]
+nu-assert = { git = "https://github.com/user/nu-assert", tag = "v0.3.0" }
+"#;
+        let manifest = Manifest::from_str(toml).unwrap();
+        assert_eq!(manifest.dependencies.len(), 1);
+        assert!(manifest.dev_dependencies.contains_key("nu-assert"));
+    }
+
+    #[test]
+    fn reject_dependency_in_both_sections() {
+        let toml = r#"
+[package]
+name = "bad"
+version = "0.1.0"
+
+[dependencies]
+dup = { git = "https://github.com/user/dup", tag = "v1" }
+
+[dev-dependencies]
+dup = { git = "https://github.com/user/dup", tag = "v1" }
+"#;
+        let err = Manifest::from_str(toml).unwrap_err();
+        assert!(err.to_string().contains("declared in both"));
+    }
+
     #[test]
     fn reject_empty_name() {
         let toml = r#"
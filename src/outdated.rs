@@ -0,0 +1,268 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::error::{NuanceError, Result};
+use crate::git::{self, RefKind};
+use crate::lockfile::{LockedPackage, Lockfile};
+use crate::manifest::DependencySpec;
+use crate::version;
+
+/// A locked dependency with a newer release or newer commits available.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OutdatedDep {
+    pub name: String,
+    /// How the dependency is requested: `version`, `tag` or `branch`.
+    pub kind: &'static str,
+    /// The locked tag, or the abbreviated locked commit for branches.
+    pub current: String,
+    /// The newest tag allowed by the requirement (or the branch tip).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compatible: Option<String>,
+    /// The newest tag overall (or the branch tip).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<String>,
+    /// Commits on the branch since the locked one (branch dependencies only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_commits: Option<usize>,
+}
+
+/// Check every git package in a lockfile for newer tags or commits.
+///
+/// Path dependencies and dependencies pinned to a `rev` are never outdated.
+pub fn check(lock_path: &Path) -> Result<Vec<OutdatedDep>> {
+    if !lock_path.exists() {
+        return Err(NuanceError::Lockfile(format!(
+            "{} not found; run `nuance install` first",
+            lock_path
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default()
+        )));
+    }
+    let lockfile = Lockfile::from_path(lock_path)?;
+
+    let repos = git::clone_or_fetch_all(lockfile.packages.iter().filter_map(|p| p.git.as_deref()))?;
+    let mut outdated = Vec::new();
+    for pkg in &lockfile.packages {
+        let Some(url) = &pkg.git else { continue };
+        if let Some(dep) = compare(&repos[url], pkg)? {
+            outdated.push(dep);
+        }
+    }
+    Ok(outdated)
+}
+
+/// Compare a locked package with the tags and branches in its repository.
+fn compare(repo_path: &Path, pkg: &LockedPackage) -> Result<Option<OutdatedDep>> {
+    // Entries written before the requested spec was recorded are treated as tag pins
+    let fallback = DependencySpec {
+        tag: pkg.tag.clone(),
+        ..Default::default()
+    };
+    let spec = pkg.requested.as_ref().unwrap_or(&fallback);
+
+    if let Some(branch) = &spec.branch {
+        let Some(rev) = &pkg.rev else {
+            return Ok(None);
+        };
+        let tip = git::resolve_ref(repo_path, branch, RefKind::Branch)?;
+        if &tip == rev {
+            return Ok(None);
+        }
+        let new_commits = git::count_commits_between(repo_path, rev, &tip)?;
+        let tip_label = format!("{branch}@{}", short_rev(&tip));
+        return Ok(Some(OutdatedDep {
+            name: pkg.name.clone(),
+            kind: "branch",
+            current: format!("{branch}@{}", short_rev(rev)),
+            compatible: Some(tip_label.clone()),
+            latest: Some(tip_label),
+            new_commits: Some(new_commits),
+        }));
+    }
+
+    // Only tag-based dependencies can be compared by version
+    let (Some(current), None) = (&pkg.tag, &spec.rev) else {
+        return Ok(None);
+    };
+    let Some(current_version) = version::parse_tag(current) else {
+        return Ok(None);
+    };
+
+    let (kind, req) = match &spec.version {
+        Some(req) => ("version", version::parse_req(&pkg.name, req)?),
+        None => (
+            "tag",
+            version::parse_req(&pkg.name, &format!("^{current_version}"))?,
+        ),
+    };
+
+    let tags = git::list_tags(repo_path)?;
+    let newer = |tag: Option<&str>| {
+        tag.filter(|t| version::parse_tag(t).is_some_and(|v| v > current_version))
+            .map(str::to_string)
+    };
+    let compatible = newer(version::highest_matching(&tags, &req));
+    let latest = newer(version::highest(&tags, !current_version.pre.is_empty()));
+
+    if compatible.is_none() && latest.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(OutdatedDep {
+        name: pkg.name.clone(),
+        kind,
+        current: current.clone(),
+        compatible,
+        latest,
+        new_commits: None,
+    }))
+}
+
+/// Print outdated dependencies as an aligned table.
+pub fn print_table(deps: &[OutdatedDep]) {
+    let header = ["Package", "Current", "Compatible", "Latest", "Kind"];
+    let rows: Vec<[String; 5]> = deps
+        .iter()
+        .map(|dep| {
+            let latest = match (&dep.latest, dep.new_commits) {
+                (Some(latest), Some(n)) => {
+                    format!("{latest} (+{n} commit{})", if n == 1 { "" } else { "s" })
+                }
+                (Some(latest), None) => latest.clone(),
+                (None, _) => "-".to_string(),
+            };
+            [
+                dep.name.clone(),
+                dep.current.clone(),
+                dep.compatible.clone().unwrap_or_else(|| "-".to_string()),
+                latest,
+                dep.kind.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let print_row = |cells: &[&str]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(&header);
+    for row in &rows {
+        print_row(&row.each_ref().map(String::as_str));
+    }
+}
+
+/// Print outdated dependencies as a JSON array.
+pub fn print_json(deps: &[OutdatedDep]) -> Result<()> {
+    let json = serde_json::to_string_pretty(deps).map_err(|e| NuanceError::Other(e.to_string()))?;
+    println!("{json}");
+    Ok(())
+}
+
+fn short_rev(rev: &str) -> &str {
+    &rev[..7.min(rev.len())]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lockfile::SourceKind;
+    use crate::test_support::{commit, tag, temp_dir};
+    use git2::Repository;
+
+    fn locked(
+        name: &str,
+        tag: Option<&str>,
+        rev: git2::Oid,
+        spec: DependencySpec,
+    ) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            source: SourceKind::Git,
+            git: Some("https://example.com/dep".to_string()),
+            path: None,
+            subdir: None,
+            branch: spec.branch.clone(),
+            tag: tag.map(str::to_string),
+            rev: Some(rev.to_string()),
+            sha256: String::new(),
+            dev: false,
+            dependencies: Vec::new(),
+            requested: Some(spec),
+        }
+    }
+
+    #[test]
+    fn reports_compatible_and_latest_tags() {
+        let dir = temp_dir("tags");
+        let repo = Repository::init(&dir).unwrap();
+        for (i, name) in ["v1.0.0", "v1.1.0", "v2.0.0"].iter().enumerate() {
+            let oid = commit(&repo, &[("mod.nu", &i.to_string())]);
+            tag(&repo, name, oid);
+        }
+        let v1 = repo.revparse_single("v1.0.0").unwrap().id();
+
+        let spec = DependencySpec {
+            version: Some("^1.0".to_string()),
+            ..Default::default()
+        };
+        let dep = compare(&dir, &locked("dep", Some("v1.0.0"), v1, spec))
+            .unwrap()
+            .unwrap();
+        assert_eq!(dep.kind, "version");
+        assert_eq!(dep.compatible.as_deref(), Some("v1.1.0"));
+        assert_eq!(dep.latest.as_deref(), Some("v2.0.0"));
+
+        let v2 = repo.revparse_single("v2.0.0").unwrap().id();
+        let spec = DependencySpec {
+            tag: Some("v2.0.0".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            compare(&dir, &locked("dep", Some("v2.0.0"), v2, spec)).unwrap(),
+            None
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn reports_new_commits_on_branch() {
+        let dir = temp_dir("branch");
+        let repo = Repository::init(&dir).unwrap();
+        let first = commit(&repo, &[("mod.nu", "1")]);
+        commit(&repo, &[("mod.nu", "2")]);
+        let tip = commit(&repo, &[("mod.nu", "3")]);
+        let head = repo.head().unwrap();
+        let branch = head.shorthand().unwrap().to_string();
+        repo.reference(&format!("refs/remotes/origin/{branch}"), tip, true, "test")
+            .unwrap();
+
+        let spec = DependencySpec {
+            branch: Some(branch.clone()),
+            ..Default::default()
+        };
+        let dep = compare(&dir, &locked("dep", None, first, spec))
+            .unwrap()
+            .unwrap();
+        assert_eq!(dep.kind, "branch");
+        assert_eq!(dep.new_commits, Some(2));
+        assert_eq!(
+            dep.latest,
+            Some(format!("{branch}@{}", short_rev(&tip.to_string())))
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,309 @@
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::cache;
+
+/// How often progress bars are redrawn on a terminal.
+const BAR_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often a plain progress line is printed when stderr is not a
+/// terminal. Transfers that finish sooner print nothing.
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Width of the bar itself, in characters.
+const BAR_WIDTH: usize = 24;
+
+/// The longest repository name shown before it is cut short.
+const LABEL_WIDTH: usize = 20;
+
+/// One repository being fetched.
+#[derive(Debug)]
+struct Transfer {
+    id: u64,
+    label: String,
+    started: Instant,
+    received_objects: usize,
+    total_objects: usize,
+    indexed_deltas: usize,
+    total_deltas: usize,
+    received_bytes: usize,
+    /// The latest progress message from the remote, e.g. `Counting objects`.
+    message: String,
+}
+
+/// Every transfer in flight, and what was last drawn for them.
+struct Board {
+    transfers: Vec<Transfer>,
+    /// Lines of bars currently on screen.
+    drawn: usize,
+    last_draw: Option<Instant>,
+}
+
+static BOARD: Mutex<Board> = Mutex::new(Board {
+    transfers: Vec::new(),
+    drawn: 0,
+    last_draw: None,
+});
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+fn is_tty() -> bool {
+    static TTY: OnceLock<bool> = OnceLock::new();
+    *TTY.get_or_init(|| std::io::stderr().is_terminal())
+}
+
+fn board() -> std::sync::MutexGuard<'static, Board> {
+    BOARD.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A transfer shown in the progress display until it is dropped.
+#[derive(Debug)]
+pub struct Handle {
+    id: u64,
+}
+
+/// Start showing progress for fetching the repository `label`.
+pub fn start(label: &str) -> Handle {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    board().transfers.push(Transfer {
+        id,
+        label: label.to_string(),
+        started: Instant::now(),
+        received_objects: 0,
+        total_objects: 0,
+        indexed_deltas: 0,
+        total_deltas: 0,
+        received_bytes: 0,
+        message: String::new(),
+    });
+    Handle { id }
+}
+
+impl Handle {
+    /// Record transfer progress reported by git.
+    pub fn update(&self, stats: &git2::Progress<'_>) {
+        self.with_transfer(|t| {
+            t.received_objects = stats.received_objects();
+            t.total_objects = stats.total_objects();
+            t.indexed_deltas = stats.indexed_deltas();
+            t.total_deltas = stats.total_deltas();
+            t.received_bytes = stats.received_bytes();
+        });
+    }
+
+    /// Record a progress message sent by the remote.
+    pub fn message(&self, data: &[u8]) {
+        let text = String::from_utf8_lossy(data);
+        let Some(line) = text
+            .rsplit(['\r', '\n'])
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+        else {
+            return;
+        };
+        let line = line.to_string();
+        self.with_transfer(|t| t.message = line);
+    }
+
+    fn with_transfer(&self, f: impl FnOnce(&mut Transfer)) {
+        let mut board = board();
+        if let Some(transfer) = board.transfers.iter_mut().find(|t| t.id == self.id) {
+            f(transfer);
+        }
+        refresh(&mut board);
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let mut board = board();
+        board.transfers.retain(|t| t.id != self.id);
+        if is_tty() {
+            redraw(&mut board, None);
+        }
+    }
+}
+
+/// Print a line of output without garbling the progress bars.
+pub fn println(line: &str) {
+    let mut board = board();
+    if is_tty() && board.drawn > 0 {
+        redraw(&mut board, Some(line));
+    } else {
+        eprintln!("{line}");
+    }
+}
+
+/// Redraw the bars, or print a plain summary, if enough time has passed.
+fn refresh(board: &mut Board) {
+    let now = Instant::now();
+    let since_draw = board.last_draw.map(|t| now - t);
+    if is_tty() {
+        if since_draw.is_none_or(|d| d >= BAR_INTERVAL) {
+            redraw(board, None);
+        }
+        return;
+    }
+
+    let running = board
+        .transfers
+        .iter()
+        .filter(|t| now - t.started >= PLAIN_INTERVAL)
+        .map(describe)
+        .collect::<Vec<_>>();
+    if !running.is_empty() && since_draw.is_none_or(|d| d >= PLAIN_INTERVAL) {
+        eprintln!("  Still fetching: {}", running.join(", "));
+        board.last_draw = Some(now);
+    }
+}
+
+/// Replace the bars on screen with the current ones, printing `line`
+/// above them first.
+fn redraw(board: &mut Board, line: Option<&str>) {
+    let mut out = String::new();
+    if board.drawn > 0 {
+        // Move to the first bar and clear everything below it
+        out.push_str(&format!("\x1b[{}A\x1b[J", board.drawn));
+    }
+    if let Some(line) = line {
+        out.push_str(line);
+        out.push('\n');
+    }
+    for transfer in &board.transfers {
+        out.push_str(&bar(transfer));
+        out.push('\n');
+    }
+    if board.transfers.len() > 1 {
+        out.push_str(&total(&board.transfers));
+        out.push('\n');
+    }
+    board.drawn = match board.transfers.len() {
+        0 => 0,
+        1 => 1,
+        n => n + 1,
+    };
+    board.last_draw = Some(Instant::now());
+
+    let mut stderr = std::io::stderr().lock();
+    let _ = stderr.write_all(out.as_bytes());
+    let _ = stderr.flush();
+}
+
+/// A progress bar line, e.g.
+/// `  nu-utils             [##########..............]  42% 1.2 MiB receiving`.
+fn bar(transfer: &Transfer) -> String {
+    let label = short_label(&transfer.label);
+    if transfer.total_objects == 0 {
+        let message = transfer
+            .message
+            .chars()
+            .take(BAR_WIDTH + 20)
+            .collect::<String>();
+        return format!("  {label:<LABEL_WIDTH$} {message}");
+    }
+
+    let (done, total, what) = phase(transfer);
+    let filled = BAR_WIDTH * done / total.max(1);
+    format!(
+        "  {label:<LABEL_WIDTH$} [{}{}] {:>3}% {} {what}",
+        "#".repeat(filled),
+        ".".repeat(BAR_WIDTH - filled),
+        100 * done / total.max(1),
+        cache::format_size(transfer.received_bytes as u64),
+    )
+}
+
+/// A one-line summary for plain output, e.g.
+/// `nu-utils 42% receiving (1.2 MiB)`.
+fn describe(transfer: &Transfer) -> String {
+    if transfer.total_objects == 0 {
+        return format!("{} (waiting for the remote)", transfer.label);
+    }
+    let (done, total, what) = phase(transfer);
+    format!(
+        "{} {}% {what} ({})",
+        transfer.label,
+        100 * done / total.max(1),
+        cache::format_size(transfer.received_bytes as u64)
+    )
+}
+
+/// The line summing up several transfers.
+fn total(transfers: &[Transfer]) -> String {
+    let bytes: usize = transfers.iter().map(|t| t.received_bytes).sum();
+    format!(
+        "  {} repositories, {} received",
+        transfers.len(),
+        cache::format_size(bytes as u64)
+    )
+}
+
+/// How far along a transfer is: receiving objects, then resolving deltas.
+fn phase(transfer: &Transfer) -> (usize, usize, &'static str) {
+    if transfer.received_objects < transfer.total_objects || transfer.total_deltas == 0 {
+        (
+            transfer.received_objects,
+            transfer.total_objects,
+            "receiving",
+        )
+    } else {
+        (transfer.indexed_deltas, transfer.total_deltas, "resolving")
+    }
+}
+
+fn short_label(label: &str) -> String {
+    if label.chars().count() <= LABEL_WIDTH {
+        return label.to_string();
+    }
+    let mut short: String = label.chars().take(LABEL_WIDTH - 1).collect();
+    short.push('…');
+    short
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_bars_and_summaries() {
+        let mut transfer = Transfer {
+            id: 0,
+            label: "nu-utils".to_string(),
+            started: Instant::now(),
+            received_objects: 0,
+            total_objects: 0,
+            indexed_deltas: 0,
+            total_deltas: 0,
+            received_bytes: 0,
+            message: "Counting objects:  50% (5/10)".to_string(),
+        };
+        assert!(bar(&transfer).ends_with("Counting objects:  50% (5/10)"));
+        assert_eq!(describe(&transfer), "nu-utils (waiting for the remote)");
+
+        transfer.received_objects = 50;
+        transfer.total_objects = 100;
+        transfer.received_bytes = 1536;
+        assert_eq!(
+            bar(&transfer),
+            format!(
+                "  {:<20} [{}{}]  50% 1.5 KiB receiving",
+                "nu-utils",
+                "#".repeat(12),
+                ".".repeat(12)
+            )
+        );
+        assert_eq!(describe(&transfer), "nu-utils 50% receiving (1.5 KiB)");
+
+        transfer.received_objects = 100;
+        transfer.total_deltas = 40;
+        transfer.indexed_deltas = 10;
+        assert_eq!(describe(&transfer), "nu-utils 25% resolving (1.5 KiB)");
+
+        assert_eq!(
+            short_label("a-very-long-repository-name"),
+            "a-very-long-reposit…"
+        );
+    }
+}
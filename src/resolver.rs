@@ -1,42 +1,115 @@
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::path::{Component, Path, PathBuf};
 
 use crate::checksum;
 use crate::error::{NuanceError, Result};
-use crate::git::{self, RefKind};
+use crate::git::{self, FetchTarget, RefKind};
+use crate::jobs;
 use crate::lockfile::LockedPackage;
 use crate::manifest::{DependencySpec, Manifest};
+use crate::progress;
+use crate::version;
 
 /// A fully resolved dependency.
+///
+/// Git dependencies carry a URL and commit; path dependencies carry the
+/// directory relative to the project root and no commit.
 #[derive(Debug, Clone)]
 pub struct ResolvedDep {
     pub name: String,
-    pub git: String,
+    pub git: Option<String>,
+    pub path: Option<String>,
+    pub subdir: Option<String>,
     pub tag: Option<String>,
-    pub rev: String,
+    pub rev: Option<String>,
+    /// Only reachable through the root's `[dev-dependencies]`.
+    pub dev: bool,
+    /// The dependency spec this package was resolved from.
+    pub requested: Option<DependencySpec>,
+    /// Names of the packages this one depends on directly, sorted.
+    pub dependencies: Vec<String>,
+}
+
+/// The name used for the project itself in requirement chains.
+const ROOT: &str = "root";
+
+/// Packages that `nuance update` should move; everything else stays locked.
+#[derive(Debug, Clone, Default)]
+pub struct Unlock {
+    /// Packages to re-resolve even though their spec is unchanged.
+    pub names: HashSet<String>,
+    /// Also re-resolve the dependencies of `names` at their locked commits.
+    pub recursive: bool,
+    /// Move the single package in `names` to this exact tag or commit.
+    pub precise: Option<String>,
 }
 
 /// Resolve all dependencies (including transitive) from a root manifest.
 ///
-/// Returns a flat map of package name → resolved dependency.
-/// Errors on conflicts (same name, different source or rev).
-pub fn resolve(root_dir: &Path) -> Result<Vec<ResolvedDep>> {
+/// The root's dev-dependencies are included and marked as dev-only unless
+/// they are also reachable from a regular dependency. Dev-dependencies of
+/// dependencies are never resolved.
+///
+/// Packages in `locked` whose requested spec is unchanged keep their locked
+/// commit unless named in `unlock`; everything else is resolved afresh.
+///
+/// Returns a flat list of resolved dependencies, sorted by name.
+/// Errors when no set of versions satisfies every requirement, or when
+/// packages depend on each other in a cycle.
+pub fn resolve(
+    root_dir: &Path,
+    locked: &[LockedPackage],
+    unlock: &Unlock,
+) -> Result<Vec<ResolvedDep>> {
+    resolve_root(root_dir, locked, unlock, true)
+}
+
+/// Like [`resolve`], but leaves the root's dev-dependencies out entirely,
+/// so their repositories are never fetched.
+pub fn resolve_without_dev(
+    root_dir: &Path,
+    locked: &[LockedPackage],
+    unlock: &Unlock,
+) -> Result<Vec<ResolvedDep>> {
+    resolve_root(root_dir, locked, unlock, false)
+}
+
+fn resolve_root(
+    root_dir: &Path,
+    locked: &[LockedPackage],
+    unlock: &Unlock,
+    with_dev: bool,
+) -> Result<Vec<ResolvedDep>> {
     let manifest = Manifest::from_dir(root_dir)?;
-    resolve_from_deps(&manifest.dependencies)
+    let own_name = &manifest.package.name;
+    let no_dev = HashMap::new();
+    let dev_deps = if with_dev {
+        &manifest.dev_dependencies
+    } else {
+        &no_dev
+    };
+    if manifest.dependencies.contains_key(own_name) || dev_deps.contains_key(own_name) {
+        return Err(NuanceError::Cycle {
+            path: vec![own_name.clone(), own_name.clone()],
+        });
+    }
+    let mut resolver = Resolver::new(root_dir, locked, unlock)?;
+    resolver.root_name = Some(own_name.clone());
+    resolver.solve_from(&manifest.dependencies, dev_deps)
 }
 
 /// Resolve dependencies from a pre-built dependency map (used by global install).
 ///
+/// Relative `path` dependencies are resolved against `base_dir`.
 /// Returns a flat list of resolved dependencies, sorted by name.
-pub fn resolve_from_deps(deps: &HashMap<String, DependencySpec>) -> Result<Vec<ResolvedDep>> {
-    let mut resolved: HashMap<String, ResolvedDep> = HashMap::new();
-
-    resolve_deps(deps, &mut resolved)?;
-
-    // Return sorted for deterministic output
-    let mut deps: Vec<_> = resolved.into_values().collect();
-    deps.sort_by(|a, b| a.name.cmp(&b.name));
-    Ok(deps)
+pub fn resolve_from_deps(
+    deps: &HashMap<String, DependencySpec>,
+    base_dir: &Path,
+    locked: &[LockedPackage],
+    unlock: &Unlock,
+) -> Result<Vec<ResolvedDep>> {
+    let mut resolver = Resolver::new(base_dir, locked, unlock)?;
+    resolver.solve_from(deps, &HashMap::new())
 }
 
 /// Resolve dependencies from an existing lockfile without re-fetching.
@@ -46,65 +119,985 @@ pub fn resolve_from_lock(locked: &[LockedPackage]) -> Vec<ResolvedDep> {
         .map(|p| ResolvedDep {
             name: p.name.clone(),
             git: p.git.clone(),
+            path: p.path.clone(),
+            subdir: p.subdir.clone(),
             tag: p.tag.clone(),
             rev: p.rev.clone(),
+            dev: p.dev,
+            requested: p.requested.clone(),
+            dependencies: p.dependencies.clone(),
         })
         .collect()
 }
 
-fn resolve_deps(
-    deps: &HashMap<String, DependencySpec>,
-    resolved: &mut HashMap<String, ResolvedDep>,
-) -> Result<()> {
-    for (name, spec) in deps {
-        // Clone or fetch the repo
-        eprintln!("  Fetching {name} from {}...", spec.git);
-        let repo_path = git::clone_or_fetch(&spec.git)?;
-
-        // Resolve the ref to a commit SHA
-        let kind = RefKind::from_spec(&spec.tag, &spec.rev, &spec.branch);
-        let rev = git::resolve_ref(&repo_path, spec.ref_spec(), kind)?;
-
-        // Check for conflicts
-        if let Some(existing) = resolved.get(name) {
-            if existing.rev != rev || existing.git != spec.git {
-                return Err(NuanceError::Conflict {
-                    name: name.clone(),
-                    rev_a: existing.rev.clone(),
-                    rev_b: rev,
+/// One package's requirement on another, as declared in a manifest.
+#[derive(Debug, Clone)]
+struct Requirement {
+    spec: DependencySpec,
+    /// Packages from the root down to the one that declared this requirement.
+    chain: Vec<String>,
+    /// Directory of the declaring manifest relative to the root, or `None`
+    /// when it came from a git package (which cannot declare path dependencies).
+    local_base: Option<PathBuf>,
+}
+
+impl Requirement {
+    /// Where this requirement wants the package to come from.
+    fn source(&self, name: &str) -> Result<Source> {
+        match (&self.spec.path, &self.spec.git) {
+            (Some(path), _) => {
+                let base = self.local_base.as_ref().ok_or_else(|| {
+                    NuanceError::Manifest(format!(
+                        "dependency '{name}': path dependencies are not allowed inside git packages"
+                    ))
+                })?;
+                let rel = normalize_path(&base.join(path));
+                Ok(Source::Path(rel.to_string_lossy().to_string()))
+            }
+            (None, Some(url)) => Ok(Source::Git {
+                url: url.clone(),
+                subdir: self.spec.subdir.clone(),
+            }),
+            (None, None) => Err(NuanceError::Manifest(format!(
+                "dependency '{name}': must specify either 'git' or 'path'"
+            ))),
+        }
+    }
+
+    /// What was asked for, e.g. `tag v1.2.0` or `version ^1.2`.
+    fn label(&self) -> String {
+        let spec = &self.spec;
+        if let Some(path) = &spec.path {
+            format!("path {path}")
+        } else if let Some(req) = &spec.version {
+            format!("version {req}")
+        } else if let Some(tag) = &spec.tag {
+            format!("tag {tag}")
+        } else if let Some(branch) = &spec.branch {
+            format!("branch {branch}")
+        } else {
+            let rev = spec.ref_spec();
+            format!("rev {}", &rev[..7.min(rev.len())])
+        }
+    }
+
+    fn required_by(&self) -> String {
+        self.chain.join(" → ")
+    }
+}
+
+/// Where a package comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Source {
+    Git {
+        url: String,
+        subdir: Option<String>,
+    },
+    /// A directory relative to the root.
+    Path(String),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Git { url, subdir: None } => write!(f, "{url}"),
+            Source::Git {
+                url,
+                subdir: Some(subdir),
+            } => write!(f, "{url} (subdir {subdir})"),
+            Source::Path(path) => write!(f, "path {path}"),
+        }
+    }
+}
+
+/// A concrete version a package can be resolved to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Candidate {
+    source: Source,
+    tag: Option<String>,
+    /// The commit, for git packages.
+    rev: Option<String>,
+}
+
+impl Candidate {
+    fn same_version(&self, other: &Candidate) -> bool {
+        self.source == other.source && self.rev == other.rev
+    }
+}
+
+/// A partial solution: what each package is required to be, and the versions
+/// chosen so far.
+#[derive(Debug, Clone, Default)]
+struct State {
+    requirements: BTreeMap<String, Vec<Requirement>>,
+    decisions: BTreeMap<String, Decision>,
+}
+
+#[derive(Debug, Clone)]
+struct Decision {
+    candidate: Candidate,
+    dependencies: Vec<String>,
+}
+
+/// Where a step of the search led.
+enum Outcome {
+    /// A state with the package decided (or, from `solve`, every package).
+    Solved(State),
+    /// A dead end, caused by the decisions of these packages. Trying other
+    /// versions of any package not listed can't get past it.
+    Conflict(BTreeSet<String>),
+}
+
+/// A dead end found while solving, kept to explain a failed resolution.
+///
+/// Requirements that can never be satisfied together are `definite`; a
+/// requirement that only clashes with an earlier choice may be resolved by
+/// backtracking, so it only explains the failure when nothing better is known.
+struct DeadEnd {
+    error: NuanceError,
+    definite: bool,
+}
+
+/// State for a single resolution run.
+///
+/// Packages are decided one at a time in name order. Each decision picks the
+/// most preferred candidate (the locked commit, then the highest matching
+/// version) that satisfies every requirement on the package so far, then adds
+/// that version's own requirements. When a package has no acceptable
+/// candidate, the solver jumps back to the most recent decision involved in
+/// the conflict and tries its next candidate, so the result never depends on
+/// hash order.
+struct Resolver<'a> {
+    /// Directory that relative path dependencies are resolved against.
+    root_dir: &'a Path,
+    /// Previously locked packages, kept when their requested spec is unchanged.
+    locked: &'a [LockedPackage],
+    unlock: Unlock,
+    /// The root package's name, so a dependency back on it is a cycle.
+    root_name: Option<String>,
+    /// Repositories fetched in full during this run, by URL.
+    fetched: HashMap<String, PathBuf>,
+    /// Single tags, branches and commits fetched during this run, by URL.
+    fetched_refs: HashSet<(String, FetchTarget)>,
+    /// Manifests read for each candidate, so backtracking doesn't re-export.
+    manifests: HashMap<Candidate, Option<Manifest>>,
+    /// Versions each requirement accepts once its repository is fetched, so
+    /// backtracking doesn't list and resolve the tags again.
+    allowed: HashMap<(String, Source, DependencySpec), Vec<Candidate>>,
+    dead_end: Option<DeadEnd>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(root_dir: &'a Path, locked: &'a [LockedPackage], unlock: &Unlock) -> Result<Self> {
+        let mut resolver = Self {
+            root_dir,
+            locked,
+            unlock: unlock.clone(),
+            root_name: None,
+            fetched: HashMap::new(),
+            fetched_refs: HashSet::new(),
+            manifests: HashMap::new(),
+            allowed: HashMap::new(),
+            dead_end: None,
+        };
+        if unlock.recursive {
+            resolver.unlock_locked_descendants()?;
+        }
+        Ok(resolver)
+    }
+
+    /// Solve for the given root dependencies and flatten the result into a
+    /// list sorted by name.
+    fn solve_from(
+        &mut self,
+        deps: &HashMap<String, DependencySpec>,
+        dev_deps: &HashMap<String, DependencySpec>,
+    ) -> Result<Vec<ResolvedDep>> {
+        let mut state = State::default();
+        for (name, spec) in sorted(deps).into_iter().chain(sorted(dev_deps)) {
+            state
+                .requirements
+                .entry(name.clone())
+                .or_default()
+                .push(Requirement {
+                    spec: spec.clone(),
+                    chain: vec![ROOT.to_string()],
+                    local_base: Some(PathBuf::new()),
                 });
+        }
+
+        let Outcome::Solved(solution) = self.solve(state)? else {
+            return Err(self.dead_end.take().map(|d| d.error).unwrap_or_else(|| {
+                NuanceError::Other("could not find a set of versions for all dependencies".into())
+            }));
+        };
+
+        if self.unlock.precise.is_some() {
+            for name in &self.unlock.names {
+                if !solution.decisions.contains_key(name) {
+                    return Err(NuanceError::Other(format!(
+                        "package '{name}' is not a dependency"
+                    )));
+                }
             }
-            // Same resolution — skip (already resolved)
-            continue;
         }
 
-        resolved.insert(
-            name.clone(),
-            ResolvedDep {
-                name: name.clone(),
-                git: spec.git.clone(),
-                tag: spec.tag.clone(),
-                rev: rev.clone(),
+        // Packages reachable from a regular root dependency are not dev-only
+        let mut regular = HashSet::new();
+        let mut queue: Vec<&String> = deps.keys().collect();
+        while let Some(name) = queue.pop() {
+            if regular.insert(name.clone())
+                && let Some(decision) = solution.decisions.get(name)
+            {
+                queue.extend(&decision.dependencies);
+            }
+        }
+
+        let mut resolved = Vec::new();
+        for (name, decision) in solution.decisions {
+            let requested = solution.requirements[&name][0].spec.clone();
+            let candidate = decision.candidate;
+            let (git, subdir, path) = match candidate.source {
+                Source::Git { url, subdir } => (Some(url), subdir, None),
+                Source::Path(path) => (None, None, Some(path)),
+            };
+            resolved.push(ResolvedDep {
+                dev: !regular.contains(&name),
+                name,
+                git,
+                path,
+                subdir,
+                tag: candidate.tag,
+                rev: candidate.rev,
+                requested: Some(requested),
+                dependencies: decision.dependencies,
+            });
+        }
+        Ok(resolved)
+    }
+
+    /// Decide the next undecided package, backtracking over its candidates.
+    ///
+    /// Returns a conflict naming the decisions that led to it when no choice
+    /// leads to a solution.
+    fn solve(&mut self, state: State) -> Result<Outcome> {
+        let Some(name) = state
+            .requirements
+            .keys()
+            .find(|name| !state.decisions.contains_key(*name))
+            .cloned()
+        else {
+            return Ok(Outcome::Solved(state));
+        };
+        let requirements = state.requirements[&name].clone();
+        self.prefetch(&state)?;
+
+        // The packages that require this one decide what it may be
+        let mut culprits: BTreeSet<String> = requirements
+            .iter()
+            .filter_map(|r| r.chain.last())
+            .filter(|n| *n != ROOT)
+            .cloned()
+            .collect();
+
+        // Try the locked version first, and only fetch when it doesn't work out
+        let mut tried: Vec<Candidate> = Vec::new();
+        for fresh in [false, true] {
+            for candidate in self.candidates(&name, &requirements, fresh)? {
+                if tried.iter().any(|t| t.same_version(&candidate)) {
+                    continue;
+                }
+                tried.push(candidate.clone());
+                let next = match self.decide(&state, &name, candidate)? {
+                    Outcome::Solved(next) => next,
+                    Outcome::Conflict(cause) => {
+                        culprits.extend(cause);
+                        continue;
+                    }
+                };
+                match self.solve(next)? {
+                    Outcome::Solved(solution) => return Ok(Outcome::Solved(solution)),
+                    // Another version of this package can't help: jump back
+                    // to the most recent decision that can
+                    Outcome::Conflict(cause) if !cause.contains(&name) => {
+                        return Ok(Outcome::Conflict(cause));
+                    }
+                    Outcome::Conflict(cause) => culprits.extend(cause),
+                }
+            }
+        }
+        culprits.remove(&name);
+        Ok(Outcome::Conflict(culprits))
+    }
+
+    /// Choose `candidate` for `name` and add its dependencies' requirements.
+    ///
+    /// Returns a conflict naming the already decided package that one of
+    /// those requirements clashes with, if any.
+    fn decide(&mut self, state: &State, name: &str, candidate: Candidate) -> Result<Outcome> {
+        let manifest = self.manifest_for(name, &candidate)?;
+        let deps = manifest.map(|m| m.dependencies).unwrap_or_default();
+
+        let mut chain = state.requirements[name][0].chain.clone();
+        chain.push(name.to_string());
+        let local_base = match &candidate.source {
+            Source::Path(path) => Some(PathBuf::from(path)),
+            Source::Git { .. } => None,
+        };
+        let mut next = state.clone();
+        let dependencies: Vec<String> = sorted(&deps).iter().map(|(n, _)| (*n).clone()).collect();
+        next.decisions.insert(
+            name.to_string(),
+            Decision {
+                candidate,
+                dependencies,
             },
         );
 
-        // Check for transitive dependencies
-        // Export the dep to a temp dir to read its mod.toml
-        let tmp = std::env::temp_dir().join("nuance_resolve").join(name);
-        git::export_to(&repo_path, &rev, &tmp)?;
+        for (dep_name, spec) in sorted(&deps) {
+            let requirement = Requirement {
+                spec: spec.clone(),
+                chain: chain.clone(),
+                local_base: local_base.clone(),
+            };
+            if let Some(root) = &self.root_name
+                && root == dep_name
+            {
+                let mut cycle = vec![root.clone()];
+                cycle.extend(chain[1..].iter().cloned());
+                cycle.push(root.clone());
+                return Err(NuanceError::Cycle { path: cycle });
+            }
+            if let Some(decision) = next.decisions.get(dep_name) {
+                // Nushell modules can't import each other in a loop
+                if let Some(path) = path_between(&next.decisions, dep_name, name) {
+                    let mut cycle = vec![name.to_string()];
+                    cycle.extend(path);
+                    return Err(NuanceError::Cycle { path: cycle });
+                }
+                let chosen = decision.candidate.clone();
+                if !self.allows(dep_name, &requirement, &chosen)? {
+                    let first = &next.requirements[dep_name][0];
+                    let version = chosen
+                        .tag
+                        .clone()
+                        .or_else(|| chosen.rev.as_ref().map(|r| r[..7.min(r.len())].to_string()));
+                    let error = self.conflict(dep_name, first, &requirement, version)?;
+                    self.record_dead_end(error, false);
+                    return Ok(Outcome::Conflict(BTreeSet::from([dep_name.clone()])));
+                }
+            }
+            next.requirements
+                .entry(dep_name.clone())
+                .or_default()
+                .push(requirement);
+        }
+        Ok(Outcome::Solved(next))
+    }
+
+    /// The candidates for `name` that satisfy all of `requirements`, most
+    /// preferred first.
+    ///
+    /// Without `fresh`, only the locked version is considered and nothing is
+    /// fetched; with it, the repository is fetched and every matching
+    /// version is listed.
+    fn candidates(
+        &mut self,
+        name: &str,
+        requirements: &[Requirement],
+        fresh: bool,
+    ) -> Result<Vec<Candidate>> {
+        let first = &requirements[0];
+        let source = first.source(name)?;
+        for other in &requirements[1..] {
+            if other.source(name)? != source {
+                let error = self.conflict(name, first, other, None)?;
+                self.record_dead_end(error, true);
+                return Ok(Vec::new());
+            }
+        }
+
+        let url = match &source {
+            Source::Path(path) => {
+                if self.precise_for(name).is_some() {
+                    return Err(NuanceError::Other(format!(
+                        "'{name}' is a path dependency and cannot be moved with --precise"
+                    )));
+                }
+                let dir = self.root_dir.join(path);
+                if !dir.is_dir() {
+                    return Err(NuanceError::Manifest(format!(
+                        "dependency '{name}': path '{}' is not a directory",
+                        dir.display()
+                    )));
+                }
+                return Ok(vec![Candidate {
+                    source,
+                    tag: None,
+                    rev: None,
+                }]);
+            }
+            Source::Git { url, .. } => url.clone(),
+        };
+
+        if !fresh {
+            let Some(locked) = requirements
+                .iter()
+                .find_map(|r| self.locked_for(name, &r.spec))
+            else {
+                return Ok(Vec::new());
+            };
+            let rev = locked.rev.clone().expect("checked by locked_for");
+            let candidate = Candidate {
+                source,
+                tag: locked.tag.clone(),
+                rev: Some(rev.clone()),
+            };
+            // Unchanged since the last lock: keep the locked commit if every
+            // requirement accepts it, judged by what is already cached
+            let repo_path = self.commit_repo(&url, &rev)?;
+            for requirement in requirements {
+                let allowed = self.allowed(&repo_path, name, requirement, false)?;
+                if !allowed.iter().any(|c| c.same_version(&candidate)) {
+                    return Ok(Vec::new());
+                }
+            }
+            return Ok(vec![candidate]);
+        }
 
-        if let Ok(dep_manifest) = Manifest::from_dir(&tmp) {
-            if !dep_manifest.dependencies.is_empty() {
-                eprintln!("  Resolving transitive dependencies for {name}...");
-                resolve_deps(&dep_manifest.dependencies, resolved)?;
+        let targets = requirements
+            .iter()
+            .map(|r| self.fetch_target(name, &r.spec))
+            .collect();
+        let repo_path = self.fetch(name, &url, targets)?;
+        let mut lists = Vec::new();
+        for requirement in requirements {
+            let allowed = self.allowed(&repo_path, name, requirement, true)?;
+            if allowed.is_empty() {
+                let error = match &requirement.spec.version {
+                    Some(req) => NuanceError::NoMatchingVersion {
+                        name: name.to_string(),
+                        req: req.clone(),
+                    },
+                    None => NuanceError::Other(format!(
+                        "'{name}' has no {} (required by {})",
+                        requirement.label(),
+                        requirement.required_by()
+                    )),
+                };
+                self.record_dead_end(error, true);
+                return Ok(Vec::new());
             }
+            lists.push(allowed);
         }
 
-        // Clean up temp dir
-        let _ = std::fs::remove_dir_all(&tmp);
+        let candidates: Vec<Candidate> = lists[0]
+            .iter()
+            .filter(|c| {
+                lists[1..]
+                    .iter()
+                    .all(|l| l.iter().any(|o| o.same_version(c)))
+            })
+            .cloned()
+            .collect();
+        if candidates.is_empty() {
+            let error = self.explain_no_candidates(name, requirements, &lists)?;
+            self.record_dead_end(error, true);
+        }
+        Ok(candidates)
     }
 
-    Ok(())
+    /// The versions of a git package that `requirement` accepts, most
+    /// preferred first.
+    ///
+    /// With `fresh`, a missing tag or branch is an error; otherwise the cache
+    /// may simply be out of date and nothing is accepted.
+    fn allowed(
+        &mut self,
+        repo_path: &Path,
+        name: &str,
+        requirement: &Requirement,
+        fresh: bool,
+    ) -> Result<Vec<Candidate>> {
+        let source = requirement.source(name)?;
+        if !fresh {
+            return self.list_allowed(repo_path, name, requirement, source, false);
+        }
+        let key = (name.to_string(), source.clone(), requirement.spec.clone());
+        if let Some(allowed) = self.allowed.get(&key) {
+            return Ok(allowed.clone());
+        }
+        let allowed = self.list_allowed(repo_path, name, requirement, source, true)?;
+        self.allowed.insert(key, allowed.clone());
+        Ok(allowed)
+    }
+
+    /// Look up the versions that `requirement` accepts in the repository.
+    fn list_allowed(
+        &self,
+        repo_path: &Path,
+        name: &str,
+        requirement: &Requirement,
+        source: Source,
+        fresh: bool,
+    ) -> Result<Vec<Candidate>> {
+        let spec = &requirement.spec;
+        let candidate = |tag: Option<&str>, rev: String| Candidate {
+            source: source.clone(),
+            tag: tag.map(str::to_string),
+            rev: Some(rev),
+        };
+
+        if let Some(target) = self.precise_for(name) {
+            let (tag, rev) = resolve_precise(repo_path, name, spec, target)?;
+            return Ok(vec![candidate(tag.as_deref(), rev)]);
+        }
+
+        let lookup = |spec: &str, kind: RefKind| match git::resolve_ref(repo_path, spec, kind) {
+            Ok(rev) => Ok(Some(rev)),
+            Err(_) if !fresh => Ok(None),
+            Err(e) => Err(e),
+        };
+
+        let mut allowed = Vec::new();
+        if let Some(req) = &spec.version {
+            let parsed = version::parse_req(name, req)?;
+            let mut tags: Vec<_> = git::list_tags(repo_path)?
+                .into_iter()
+                .filter_map(|tag| version::parse_tag(&tag).map(|v| (v, tag)))
+                .filter(|(v, _)| parsed.matches(v))
+                .collect();
+            // Highest version first; equal versions (v1.2 and 1.2.0) by tag name
+            tags.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            for (_, tag) in tags {
+                let rev = git::resolve_ref(repo_path, &tag, RefKind::Tag)?;
+                allowed.push(candidate(Some(&tag), rev));
+            }
+        } else if let Some(tag) = &spec.tag {
+            allowed.extend(lookup(tag, RefKind::Tag)?.map(|rev| candidate(Some(tag), rev)));
+        } else if let Some(branch) = &spec.branch {
+            allowed.extend(lookup(branch, RefKind::Branch)?.map(|rev| candidate(None, rev)));
+        } else if let Some(rev) = &spec.rev {
+            allowed.extend(lookup(rev, RefKind::Rev)?.map(|rev| candidate(None, rev)));
+        }
+
+        // A locked branch commit stays acceptable after the branch moves on
+        if let Some(locked) = self.locked_for(name, spec) {
+            let rev = locked.rev.clone().expect("checked by locked_for");
+            if !allowed
+                .iter()
+                .any(|c| c.rev.as_deref() == Some(rev.as_str()))
+            {
+                allowed.push(candidate(locked.tag.as_deref(), rev));
+            }
+        }
+        Ok(allowed)
+    }
+
+    /// Whether an already chosen candidate satisfies a new requirement.
+    fn allows(
+        &mut self,
+        name: &str,
+        requirement: &Requirement,
+        chosen: &Candidate,
+    ) -> Result<bool> {
+        if requirement.source(name)? != chosen.source {
+            return Ok(false);
+        }
+        let (Source::Git { url, .. }, Some(rev)) = (&chosen.source, &chosen.rev) else {
+            return Ok(true);
+        };
+
+        // Check against the cache first, and fetch only if that isn't enough
+        if !self.fetched.contains_key(url) {
+            let repo_path = self.commit_repo(url, rev)?;
+            let allowed = self.allowed(&repo_path, name, requirement, false)?;
+            if allowed.iter().any(|c| c.same_version(chosen)) {
+                return Ok(true);
+            }
+        }
+        let target = self.fetch_target(name, &requirement.spec);
+        let repo_path = self.fetch(name, url, vec![target])?;
+        let allowed = self.allowed(&repo_path, name, requirement, true)?;
+        Ok(allowed.iter().any(|c| c.same_version(chosen)))
+    }
+
+    /// Fetch the repositories of all undecided, unlocked packages at once,
+    /// several at a time.
+    fn prefetch(&mut self, state: &State) -> Result<()> {
+        let mut wanted: Vec<(&String, &String, Vec<FetchTarget>)> = Vec::new();
+        for (name, requirements) in &state.requirements {
+            if state.decisions.contains_key(name)
+                || requirements
+                    .iter()
+                    .any(|r| self.locked_for(name, &r.spec).is_some())
+            {
+                continue;
+            }
+            let Some(url) = &requirements[0].spec.git else {
+                continue;
+            };
+            let targets = requirements
+                .iter()
+                .map(|r| self.fetch_target(name, &r.spec))
+                .collect();
+            let targets = self.unfetched(url, targets);
+            if targets.is_empty() {
+                continue;
+            }
+            match wanted.iter_mut().find(|(_, u, _)| *u == url) {
+                Some((_, _, more)) => more.extend(targets),
+                None => wanted.push((name, url, targets)),
+            }
+        }
+
+        if !git::is_offline() {
+            for (name, url, _) in &wanted {
+                progress::println(&format!("  Fetching {name} from {url}..."));
+            }
+        }
+        let results = jobs::map(&wanted, |(_, url, targets)| git::fetch_refs(url, targets));
+        let mut missing = Vec::new();
+        for ((_, url, targets), result) in wanted.into_iter().zip(results) {
+            match result {
+                Ok(path) => self.record_fetch(url, targets, path),
+                // Offline, list every repository that isn't cached
+                Err(NuanceError::Offline { missing: urls }) => missing.extend(urls),
+                Err(e) => return Err(e),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(NuanceError::Offline { missing });
+        }
+        Ok(())
+    }
+
+    /// Fetch what `targets` need from a repository, once per run.
+    fn fetch(&mut self, name: &str, url: &str, targets: Vec<FetchTarget>) -> Result<PathBuf> {
+        let targets = self.unfetched(url, targets);
+        if targets.is_empty() {
+            return match self.fetched.get(url) {
+                Some(path) => Ok(path.clone()),
+                None => git::cached_repo(url),
+            };
+        }
+        if !git::is_offline() {
+            progress::println(&format!("  Fetching {name} from {url}..."));
+        }
+        let path = git::fetch_refs(url, &targets)?;
+        self.record_fetch(url, targets, path.clone());
+        Ok(path)
+    }
+
+    /// What to fetch from a package's repository to judge a requirement:
+    /// the single tag, branch or commit it names, or everything when a
+    /// version range (or a `--precise` target) needs the full tag list.
+    fn fetch_target(&self, name: &str, spec: &DependencySpec) -> FetchTarget {
+        if self.precise_for(name).is_some() || spec.version.is_some() {
+            FetchTarget::Full
+        } else if let Some(tag) = &spec.tag {
+            FetchTarget::Tag(tag.clone())
+        } else if let Some(branch) = &spec.branch {
+            FetchTarget::Branch(branch.clone())
+        } else if let Some(rev) = &spec.rev {
+            FetchTarget::commit(rev)
+        } else {
+            FetchTarget::Full
+        }
+    }
+
+    /// The targets not yet fetched from `url` during this run.
+    fn unfetched(&self, url: &str, targets: Vec<FetchTarget>) -> Vec<FetchTarget> {
+        if self.fetched.contains_key(url) {
+            return Vec::new();
+        }
+        let mut unfetched = Vec::new();
+        for target in targets {
+            if !unfetched.contains(&target)
+                && !self
+                    .fetched_refs
+                    .contains(&(url.to_string(), target.clone()))
+            {
+                unfetched.push(target);
+            }
+        }
+        unfetched
+    }
+
+    fn record_fetch(&mut self, url: &str, targets: Vec<FetchTarget>, path: PathBuf) {
+        if targets.contains(&FetchTarget::Full) {
+            self.fetched.insert(url.to_string(), path);
+        } else {
+            for target in targets {
+                self.fetched_refs.insert((url.to_string(), target));
+            }
+        }
+    }
+
+    /// The repository holding commit `rev` of `url`: the one fetched in full
+    /// during this run if there is one, otherwise the cache.
+    fn commit_repo(&self, url: &str, rev: &str) -> Result<PathBuf> {
+        match self.fetched.get(url) {
+            Some(path) => Ok(path.clone()),
+            None => git::ensure_commit(url, rev),
+        }
+    }
+
+    /// Read the manifest of a candidate version, if it has one.
+    fn manifest_for(&mut self, name: &str, candidate: &Candidate) -> Result<Option<Manifest>> {
+        if let Some(manifest) = self.manifests.get(candidate) {
+            return Ok(manifest.clone());
+        }
+        let manifest = match (&candidate.source, &candidate.rev) {
+            (Source::Git { url, subdir }, Some(rev)) => {
+                let repo_path = self.commit_repo(url, rev)?;
+                dependency_manifest(&repo_path, rev, subdir.as_deref())?
+            }
+            (Source::Path(path), _) => Manifest::from_dir(&self.root_dir.join(path)).ok(),
+            (Source::Git { .. }, None) => None,
+        };
+        if manifest
+            .as_ref()
+            .is_some_and(|m| !m.dependencies.is_empty())
+        {
+            progress::println(&format!(
+                "  Resolving transitive dependencies for {name}..."
+            ));
+        }
+        self.manifests.insert(candidate.clone(), manifest.clone());
+        Ok(manifest)
+    }
+
+    /// Keep the most useful explanation for why solving failed.
+    fn record_dead_end(&mut self, error: NuanceError, definite: bool) {
+        let replace = match &self.dead_end {
+            None => true,
+            Some(existing) => definite && !existing.definite,
+        };
+        if replace {
+            self.dead_end = Some(DeadEnd { error, definite });
+        }
+    }
+
+    /// Explain why no version satisfies all requirements on `name`, naming
+    /// a pair of clashing requirements when there is one.
+    fn explain_no_candidates(
+        &self,
+        name: &str,
+        requirements: &[Requirement],
+        lists: &[Vec<Candidate>],
+    ) -> Result<NuanceError> {
+        for i in 0..requirements.len() {
+            for j in i + 1..requirements.len() {
+                let overlap = lists[i]
+                    .iter()
+                    .any(|c| lists[j].iter().any(|o| o.same_version(c)));
+                if !overlap {
+                    return self.conflict(name, &requirements[i], &requirements[j], None);
+                }
+            }
+        }
+        let all = requirements
+            .iter()
+            .map(|r| format!("{} by {}", r.label(), r.required_by()))
+            .collect::<Vec<_>>()
+            .join("; ");
+        Ok(NuanceError::NoSolution {
+            name: name.to_string(),
+            requirements: all,
+        })
+    }
+
+    /// Build the error for two incompatible requirements of `name`, naming
+    /// both requirement chains. Differing sources (a fork vs upstream, or git
+    /// vs path) are reported separately from differing versions. `chosen`
+    /// is the version the first requirement was resolved to, if any.
+    fn conflict(
+        &self,
+        name: &str,
+        first: &Requirement,
+        second: &Requirement,
+        chosen: Option<String>,
+    ) -> Result<NuanceError> {
+        let first_source = first.source(name)?;
+        let second_source = second.source(name)?;
+        if first_source != second_source {
+            return Ok(NuanceError::SourceConflict {
+                name: name.to_string(),
+                first: format!("{first_source} by {}", first.required_by()),
+                second: format!("{second_source} by {}", second.required_by()),
+            });
+        }
+        let first_label = match chosen {
+            Some(version) => format!("{} ({version})", first.label()),
+            None => first.label(),
+        };
+        Ok(NuanceError::Conflict {
+            name: name.to_string(),
+            first: format!("{first_label} by {}", first.required_by()),
+            second: format!("{} by {}", second.label(), second.required_by()),
+        })
+    }
+
+    /// Find a locked git package that was resolved from exactly `spec` and
+    /// isn't being updated.
+    fn locked_for(&self, name: &str, spec: &DependencySpec) -> Option<&'a LockedPackage> {
+        if self.unlock.names.contains(name) {
+            return None;
+        }
+        self.locked
+            .iter()
+            .find(|p| p.name == name && p.rev.is_some() && p.locked_from(spec))
+    }
+
+    /// Extend the unlock set with every package the named packages depend on
+    /// at their locked commits.
+    fn unlock_locked_descendants(&mut self) -> Result<()> {
+        let mut queue: Vec<String> = self.unlock.names.iter().cloned().collect();
+        while let Some(name) = queue.pop() {
+            let Some(pkg) = self.locked.iter().find(|p| p.name == name) else {
+                continue;
+            };
+            let manifest = match (&pkg.git, &pkg.rev, &pkg.path) {
+                (Some(url), Some(rev), _) => {
+                    let repo_path = self.commit_repo(url, rev)?;
+                    dependency_manifest(&repo_path, rev, pkg.subdir.as_deref())?
+                }
+                (None, None, Some(path)) => Manifest::from_dir(&self.root_dir.join(path)).ok(),
+                _ => None,
+            };
+            for child in manifest
+                .into_iter()
+                .flat_map(|m| m.dependencies.into_keys())
+            {
+                if self.unlock.names.insert(child.clone()) {
+                    queue.push(child);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The `--precise` target, if it applies to `name`.
+    fn precise_for(&self, name: &str) -> Option<&str> {
+        self.unlock
+            .precise
+            .as_deref()
+            .filter(|_| self.unlock.names.contains(name))
+    }
+}
+
+/// The shortest path of decided packages leading from `from` to `to`, if any.
+fn path_between(
+    decisions: &BTreeMap<String, Decision>,
+    from: &str,
+    to: &str,
+) -> Option<Vec<String>> {
+    let mut parents: HashMap<&str, &str> = HashMap::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(current) = queue.pop_front() {
+        if current == to {
+            let mut path = vec![current.to_string()];
+            let mut node = current;
+            while let Some(parent) = parents.get(node) {
+                path.push(parent.to_string());
+                node = parent;
+            }
+            path.reverse();
+            return Some(path);
+        }
+        let children = decisions
+            .get(current)
+            .map(|d| d.dependencies.as_slice())
+            .unwrap_or_default();
+        for child in children {
+            if child != from && !parents.contains_key(child.as_str()) {
+                parents.insert(child, current);
+                queue.push_back(child);
+            }
+        }
+    }
+    None
+}
+
+/// Dependencies in name order, so solving never depends on hash order.
+fn sorted(deps: &HashMap<String, DependencySpec>) -> Vec<(&String, &DependencySpec)> {
+    let mut deps: Vec<_> = deps.iter().collect();
+    deps.sort_by(|a, b| a.0.cmp(b.0));
+    deps
+}
+
+/// Read the `mod.toml` of a git package at a given commit, if it has one.
+///
+/// The manifest is read straight from the commit's tree, so nothing is
+/// checked out.
+fn dependency_manifest(
+    repo_path: &Path,
+    rev: &str,
+    subdir: Option<&str>,
+) -> Result<Option<Manifest>> {
+    let contents = git::read_file_at(repo_path, rev, subdir, "mod.toml")?;
+    Ok(contents.and_then(|c| Manifest::from_str(&c).ok()))
+}
+
+/// Resolve a `--precise` target for a dependency, checking that it is
+/// compatible with what the manifest asks for.
+///
+/// Returns the tag (if the target is a tag) and the commit SHA.
+fn resolve_precise(
+    repo_path: &Path,
+    name: &str,
+    spec: &DependencySpec,
+    target: &str,
+) -> Result<(Option<String>, String)> {
+    let is_tag = git::list_tags(repo_path)?.iter().any(|t| t == target);
+
+    if let Some(ref req) = spec.version {
+        let parsed = version::parse_req(name, req)?;
+        let matches = is_tag && version::parse_tag(target).is_some_and(|v| parsed.matches(&v));
+        if !matches {
+            return Err(NuanceError::Other(format!(
+                "'{target}' is not a tag of '{name}' matching its version requirement '{req}'"
+            )));
+        }
+    } else if spec.branch.is_none() {
+        return Err(NuanceError::Other(format!(
+            "'{name}' is pinned to {} '{}' in the manifest; edit the manifest to move it",
+            if spec.tag.is_some() { "tag" } else { "rev" },
+            spec.ref_spec()
+        )));
+    }
+
+    if is_tag {
+        let rev = git::resolve_ref(repo_path, target, RefKind::Tag)?;
+        Ok((Some(target.to_string()), rev))
+    } else {
+        let rev = git::resolve_commit(repo_path, target)?;
+        Ok((None, rev))
+    }
+}
+
+/// Lexically normalize a path, folding `.` and `..` components without
+/// touching the filesystem (so `a/../b` becomes `b`).
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) {
+                    normalized.pop();
+                } else {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
 }
 
 /// Compute the SHA-256 checksum of an exported dependency directory.
@@ -115,6 +1108,7 @@ pub fn compute_checksum(dir: &Path) -> Result<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::{commit, tag, temp_dir};
 
     #[test]
     fn conflict_detection() {
@@ -123,9 +1117,14 @@ mod tests {
             "my-dep".to_string(),
             ResolvedDep {
                 name: "my-dep".to_string(),
-                git: "https://github.com/user/my-dep".to_string(),
+                git: Some("https://github.com/user/my-dep".to_string()),
+                path: None,
+                subdir: None,
                 tag: Some("v1.0.0".to_string()),
-                rev: "aaaa".to_string(),
+                rev: Some("aaaa".to_string()),
+                dev: false,
+                requested: None,
+                dependencies: Vec::new(),
             },
         );
 
@@ -134,10 +1133,9 @@ mod tests {
         deps.insert(
             "my-dep".to_string(),
             DependencySpec {
-                git: "https://github.com/user/my-dep".to_string(),
+                git: Some("https://github.com/user/my-dep".to_string()),
                 tag: Some("v2.0.0".to_string()),
-                rev: None,
-                branch: None,
+                ..Default::default()
             },
         );
 
@@ -147,4 +1145,392 @@ mod tests {
         // For now, just verify the data structure works
         assert!(resolved.contains_key("my-dep"));
     }
+
+    #[test]
+    fn normalizes_relative_paths() {
+        assert_eq!(
+            normalize_path(Path::new("../nu-common/../nu-base")),
+            PathBuf::from("../nu-base")
+        );
+        assert_eq!(
+            normalize_path(Path::new("./libs/./nu-str")),
+            PathBuf::from("libs/nu-str")
+        );
+        assert_eq!(
+            normalize_path(Path::new("../../x")),
+            PathBuf::from("../../x")
+        );
+    }
+
+    #[test]
+    fn precise_target_must_fit_the_manifest_spec() {
+        let dir = temp_dir("precise");
+        let repo = git2::Repository::init(&dir).unwrap();
+        let oid = commit(&repo, &[("mod.nu", "# dep\n")]);
+        tag(&repo, "v1.2.0", oid);
+        tag(&repo, "v2.0.0", oid);
+        let sha = oid.to_string();
+
+        let versioned = DependencySpec {
+            git: Some("https://example.com/dep".to_string()),
+            version: Some("^1.0".to_string()),
+            ..Default::default()
+        };
+        let (tag, rev) = resolve_precise(&dir, "dep", &versioned, "v1.2.0").unwrap();
+        assert_eq!(tag.as_deref(), Some("v1.2.0"));
+        assert_eq!(rev, sha);
+        assert!(resolve_precise(&dir, "dep", &versioned, "v2.0.0").is_err());
+
+        let branch = DependencySpec {
+            git: Some("https://example.com/dep".to_string()),
+            branch: Some("main".to_string()),
+            ..Default::default()
+        };
+        let (tag, rev) = resolve_precise(&dir, "dep", &branch, &sha[..8]).unwrap();
+        assert_eq!(tag, None);
+        assert_eq!(rev, sha);
+
+        let pinned = DependencySpec {
+            git: Some("https://example.com/dep".to_string()),
+            tag: Some("v1.2.0".to_string()),
+            ..Default::default()
+        };
+        let err = resolve_precise(&dir, "dep", &pinned, "v2.0.0").unwrap_err();
+        assert!(err.to_string().contains("edit the manifest"));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn path_conflict_names_both_requirement_chains() {
+        let workspace = temp_dir("chain");
+        let manifest = |name: &str, deps: &str| {
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}")
+        };
+        for (dir, contents) in [
+            (
+                "app",
+                manifest("app", "nu-api = { path = \"../nu-api\" }\n"),
+            ),
+            (
+                "nu-api",
+                manifest(
+                    "nu-api",
+                    "nu-cli = { path = \"../nu-cli\" }\nnu-http = { path = \"../http-a\" }\n",
+                ),
+            ),
+            (
+                "nu-cli",
+                manifest("nu-cli", "nu-http = { path = \"../http-b\" }\n"),
+            ),
+            ("http-a", manifest("nu-http", "")),
+            ("http-b", manifest("nu-http", "")),
+        ] {
+            std::fs::create_dir_all(workspace.join(dir)).unwrap();
+            std::fs::write(workspace.join(dir).join("mod.toml"), contents).unwrap();
+        }
+
+        let err = resolve(&workspace.join("app"), &[], &Unlock::default()).unwrap_err();
+        let message = err.to_string();
+        assert!(
+            matches!(err, NuanceError::SourceConflict { .. }),
+            "{message}"
+        );
+        assert!(message.contains("by root → nu-api"), "{message}");
+        assert!(message.contains("by root → nu-api → nu-cli"), "{message}");
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn rev_conflict_is_reported_separately_from_source_conflict() {
+        let resolver = Resolver::new(Path::new("."), &[], &Unlock::default()).unwrap();
+        let requirement = |chain: &[&str], url: &str, tag: &str| Requirement {
+            spec: DependencySpec {
+                git: Some(url.to_string()),
+                tag: Some(tag.to_string()),
+                ..Default::default()
+            },
+            chain: chain.iter().map(|n| n.to_string()).collect(),
+            local_base: None,
+        };
+        let url = "https://github.com/user/nu-http";
+        let first = requirement(&["root", "nu-api"], url, "v1.2.0");
+
+        let second = requirement(&["root", "nu-cli"], url, "v2.0.0");
+        let err = resolver.conflict("nu-http", &first, &second, None).unwrap();
+        assert_eq!(
+            err.to_string(),
+            "dependency conflict: 'nu-http' required at tag v1.2.0 by root → nu-api, and at tag v2.0.0 by root → nu-cli"
+        );
+
+        let fork = requirement(
+            &["root", "nu-cli"],
+            "https://github.com/fork/nu-http",
+            "v1.2.0",
+        );
+        let err = resolver.conflict("nu-http", &first, &fork, None).unwrap();
+        assert!(matches!(err, NuanceError::SourceConflict { .. }));
+        assert!(
+            err.to_string()
+                .contains("from https://github.com/fork/nu-http by root → nu-cli")
+        );
+    }
+
+    /// Create a repository under `workspace/name` with one commit per tag,
+    /// each carrying a `mod.toml` with the given dependencies.
+    fn tagged_package(workspace: &Path, name: &str, tags: &[(&str, &str)]) -> PathBuf {
+        let dir = workspace.join(name);
+        std::fs::create_dir_all(&dir).unwrap();
+        let repo = git2::Repository::init(&dir).unwrap();
+        for (version, deps) in tags {
+            let manifest = format!(
+                "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}"
+            );
+            let oid = commit(&repo, &[("mod.toml", &manifest)]);
+            tag(&repo, version, oid);
+        }
+        dir
+    }
+
+    /// Solve `deps` against local repositories standing in for
+    /// `https://example.com/<name>`, without touching the real cache.
+    fn solve_local(
+        workspace: &Path,
+        repos: &[&str],
+        deps: &[(&str, &str, &str)],
+    ) -> Result<Vec<ResolvedDep>> {
+        let mut resolver = Resolver::new(workspace, &[], &Unlock::default()).unwrap();
+        for name in repos {
+            resolver
+                .fetched
+                .insert(format!("https://example.com/{name}"), workspace.join(name));
+        }
+        let deps = deps
+            .iter()
+            .map(|(name, key, value)| {
+                let mut spec = DependencySpec {
+                    git: Some(format!("https://example.com/{name}")),
+                    ..Default::default()
+                };
+                match *key {
+                    "tag" => spec.tag = Some(value.to_string()),
+                    _ => spec.version = Some(value.to_string()),
+                }
+                (name.to_string(), spec)
+            })
+            .collect();
+        resolver.solve_from(&deps, &HashMap::new())
+    }
+
+    fn picked<'d>(resolved: &'d [ResolvedDep], name: &str) -> Option<&'d str> {
+        resolved
+            .iter()
+            .find(|d| d.name == name)
+            .and_then(|d| d.tag.as_deref())
+    }
+
+    #[test]
+    fn overlapping_ranges_pick_a_common_tag() {
+        let workspace = temp_dir("ranges");
+        let lib = |req: &str| {
+            format!("nu-lib = {{ git = \"https://example.com/nu-lib\", version = \"{req}\" }}\n")
+        };
+        tagged_package(&workspace, "nu-a", &[("v1.0.0", &lib("^1.2"))]);
+        tagged_package(&workspace, "nu-b", &[("v1.0.0", &lib(">=1.4"))]);
+        tagged_package(
+            &workspace,
+            "nu-lib",
+            &[
+                ("v1.2.0", ""),
+                ("v1.4.0", ""),
+                ("v1.5.1", ""),
+                ("v2.0.0", ""),
+            ],
+        );
+
+        let resolved = solve_local(
+            &workspace,
+            &["nu-a", "nu-b", "nu-lib"],
+            &[("nu-a", "tag", "v1.0.0"), ("nu-b", "tag", "v1.0.0")],
+        )
+        .unwrap();
+        assert_eq!(picked(&resolved, "nu-lib"), Some("v1.5.1"));
+        let a = resolved.iter().find(|d| d.name == "nu-a").unwrap();
+        assert_eq!(a.dependencies, vec!["nu-lib"]);
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn backtracks_to_an_older_version() {
+        let workspace = temp_dir("backtrack");
+        let lib = |req: &str| {
+            format!("nu-lib = {{ git = \"https://example.com/nu-lib\", version = \"{req}\" }}\n")
+        };
+        // nu-a v1.1.0 needs a nu-lib the project can't accept, so v1.0.0 is used
+        tagged_package(
+            &workspace,
+            "nu-a",
+            &[("v1.0.0", &lib("^1")), ("v1.1.0", &lib("^2"))],
+        );
+        tagged_package(&workspace, "nu-lib", &[("v1.3.0", ""), ("v2.0.0", "")]);
+
+        let resolved = solve_local(
+            &workspace,
+            &["nu-a", "nu-lib"],
+            &[("nu-a", "version", "^1"), ("nu-lib", "version", "^1")],
+        )
+        .unwrap();
+        assert_eq!(picked(&resolved, "nu-a"), Some("v1.0.0"));
+        assert_eq!(picked(&resolved, "nu-lib"), Some("v1.3.0"));
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn keeps_pins_from_lockfiles_without_requested_specs() {
+        let workspace = temp_dir("legacy_lock");
+        let lib = tagged_package(&workspace, "nu-lib", &[("v1.0.0", ""), ("v1.1.0", "")]);
+        let pinned = git::resolve_ref(&lib, "v1.0.0", RefKind::Tag).unwrap();
+        let url = "https://example.com/nu-lib";
+        let lockfile = crate::lockfile::Lockfile::from_str(&format!(
+            "version = 1\n\n[[package]]\nname = \"nu-lib\"\ngit = \"{url}\"\ntag = \"v1.0.0\"\nrev = \"{pinned}\"\nsha256 = \"abc\"\n"
+        ))
+        .unwrap();
+
+        let mut resolver =
+            Resolver::new(&workspace, &lockfile.packages, &Unlock::default()).unwrap();
+        resolver.fetched.insert(url.to_string(), lib);
+        let spec = DependencySpec {
+            git: Some(url.to_string()),
+            version: Some("^1".to_string()),
+            ..Default::default()
+        };
+        let deps = HashMap::from([("nu-lib".to_string(), spec)]);
+        let resolved = resolver.solve_from(&deps, &HashMap::new()).unwrap();
+        assert_eq!(picked(&resolved, "nu-lib"), Some("v1.0.0"));
+        assert_eq!(resolved[0].rev.as_deref(), Some(pinned.as_str()));
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn jumps_back_past_unrelated_decisions() {
+        let workspace = temp_dir("backjump");
+        let lib = |req: &str| {
+            format!("nu-lib = {{ git = \"https://example.com/nu-lib\", version = \"{req}\" }}\n")
+        };
+        // nu-a v1.1.0 needs a nu-lib the project can't accept; nu-b has
+        // nothing to do with it, so none of its other versions are tried
+        tagged_package(
+            &workspace,
+            "nu-a",
+            &[("v1.0.0", &lib("^1")), ("v1.1.0", &lib("^2"))],
+        );
+        tagged_package(
+            &workspace,
+            "nu-b",
+            &[("v1.0.0", ""), ("v1.1.0", ""), ("v1.2.0", "")],
+        );
+        tagged_package(&workspace, "nu-lib", &[("v1.3.0", ""), ("v2.0.0", "")]);
+
+        let mut resolver = Resolver::new(&workspace, &[], &Unlock::default()).unwrap();
+        let mut deps = HashMap::new();
+        for name in ["nu-a", "nu-b", "nu-lib"] {
+            let url = format!("https://example.com/{name}");
+            resolver.fetched.insert(url.clone(), workspace.join(name));
+            let spec = DependencySpec {
+                git: Some(url),
+                version: Some("^1".to_string()),
+                ..Default::default()
+            };
+            deps.insert(name.to_string(), spec);
+        }
+        let resolved = resolver.solve_from(&deps, &HashMap::new()).unwrap();
+        assert_eq!(picked(&resolved, "nu-a"), Some("v1.0.0"));
+        assert_eq!(picked(&resolved, "nu-b"), Some("v1.2.0"));
+
+        let tried_b: Vec<_> = resolver
+            .manifests
+            .keys()
+            .filter(|c| matches!(&c.source, Source::Git { url, .. } if url.ends_with("nu-b")))
+            .collect();
+        assert_eq!(tried_b.len(), 1);
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn explains_when_no_versions_fit() {
+        let workspace = temp_dir("unsolvable");
+        let lib = |req: &str| {
+            format!("nu-lib = {{ git = \"https://example.com/nu-lib\", version = \"{req}\" }}\n")
+        };
+        tagged_package(&workspace, "nu-a", &[("v1.0.0", &lib("^1"))]);
+        tagged_package(&workspace, "nu-b", &[("v1.0.0", &lib("^2"))]);
+        tagged_package(&workspace, "nu-lib", &[("v1.3.0", ""), ("v2.0.0", "")]);
+
+        let err = solve_local(
+            &workspace,
+            &["nu-a", "nu-b", "nu-lib"],
+            &[("nu-a", "tag", "v1.0.0"), ("nu-b", "tag", "v1.0.0")],
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "dependency conflict: 'nu-lib' required at version ^1 by root → nu-a, and at version ^2 by root → nu-b"
+        );
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
+
+    #[test]
+    fn rejects_dependency_cycles() {
+        let workspace = temp_dir("cycle");
+        let dep = |name: &str| {
+            format!("{name} = {{ git = \"https://example.com/{name}\", tag = \"v1.0.0\" }}\n")
+        };
+        tagged_package(&workspace, "nu-a", &[("v1.0.0", &dep("nu-b"))]);
+        tagged_package(&workspace, "nu-b", &[("v1.0.0", &dep("nu-a"))]);
+
+        let err =
+            solve_local(&workspace, &["nu-a", "nu-b"], &[("nu-a", "tag", "v1.0.0")]).unwrap_err();
+        assert!(matches!(err, NuanceError::Cycle { .. }));
+        assert_eq!(err.to_string(), "dependency cycle: nu-b → nu-a → nu-b");
+
+        // A package listing itself is a cycle of one
+        let app = workspace.join("app");
+        let lib = workspace.join("nu-self");
+        for (dir, name, deps) in [
+            (&app, "app", "nu-self = { path = \"../nu-self\" }\n"),
+            (&lib, "nu-self", "nu-self = { path = \".\" }\n"),
+        ] {
+            std::fs::create_dir_all(dir).unwrap();
+            let manifest = format!(
+                "[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}"
+            );
+            std::fs::write(dir.join("mod.toml"), manifest).unwrap();
+        }
+        let err = resolve(&app, &[], &Unlock::default()).unwrap_err();
+        assert_eq!(err.to_string(), "dependency cycle: nu-self → nu-self");
+
+        // So is a dependency that leads back to the root
+        let back = workspace.join("nu-back");
+        std::fs::create_dir_all(&back).unwrap();
+        std::fs::write(
+            back.join("mod.toml"),
+            "[package]\nname = \"nu-back\"\nversion = \"0.1.0\"\n\n[dependencies]\napp = { path = \"../app\" }\n",
+        )
+        .unwrap();
+        std::fs::write(
+            app.join("mod.toml"),
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\nnu-back = { path = \"../nu-back\" }\n",
+        )
+        .unwrap();
+        let err = resolve(&app, &[], &Unlock::default()).unwrap_err();
+        assert_eq!(err.to_string(), "dependency cycle: app → nu-back → app");
+
+        let _ = std::fs::remove_dir_all(workspace);
+    }
 }
@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use git2::{Oid, Repository};
+
+/// Create an empty directory under the system temp dir, unique to this
+/// process and call.
+pub fn temp_dir(label: &str) -> PathBuf {
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!(
+        "nuance_test_{}_{}_{}",
+        label,
+        std::process::id(),
+        unique
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Write `files` into the work tree of `repo` and commit everything on top
+/// of HEAD at a fixed timestamp. Returns the new commit.
+pub fn commit_at(repo: &Repository, files: &[(&str, &str)], seconds: i64) -> Oid {
+    let workdir = repo.workdir().unwrap();
+    for (path, content) in files {
+        let full = workdir.join(path);
+        std::fs::create_dir_all(full.parent().unwrap()).unwrap();
+        std::fs::write(full, content).unwrap();
+    }
+    let mut index = repo.index().unwrap();
+    index
+        .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+        .unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let sig =
+        git2::Signature::new("nuance", "nuance@example.com", &git2::Time::new(seconds, 0)).unwrap();
+    let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repo.commit(Some("HEAD"), &sig, &sig, "commit", &tree, &parents)
+        .unwrap()
+}
+
+/// Like [`commit_at`], timestamped now.
+pub fn commit(repo: &Repository, files: &[(&str, &str)]) -> Oid {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    commit_at(repo, files, now as i64)
+}
+
+/// Point the lightweight tag `name` at `oid`.
+pub fn tag(repo: &Repository, name: &str, oid: Oid) {
+    let obj = repo.find_object(oid, None).unwrap();
+    repo.tag_lightweight(name, &obj, false).unwrap();
+}
@@ -0,0 +1,231 @@
+use std::path::Path;
+
+use crate::error::{NuanceError, Result};
+use crate::git;
+use crate::manifest::DependencySpec;
+use crate::progress;
+use crate::version;
+
+/// A planned change to one dependency's spec in the manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Upgrade {
+    pub name: String,
+    /// The previous tag, version requirement or rev.
+    pub from: String,
+    /// The new tag or version requirement.
+    pub to: String,
+    pub spec: DependencySpec,
+}
+
+/// The result of planning an upgrade.
+#[derive(Debug, Default)]
+pub struct Plan {
+    /// Manifest edits to make.
+    pub upgrades: Vec<Upgrade>,
+    /// Dependencies to re-resolve afterwards, sorted by name.
+    pub relock: Vec<String>,
+}
+
+/// Work out how to upgrade the given root dependencies.
+///
+/// Tag pins move to the newest semver tag (within the current major with
+/// `compatible`) and version requirements are widened to the newest release
+/// when it falls outside them. Branch and rev dependencies are only touched
+/// when listed in `names`: a branch is re-resolved to its tip and a rev moves
+/// to the newest tag. Path dependencies are never upgraded.
+pub fn plan<'a, I>(deps: I, names: &[String], compatible: bool) -> Result<Plan>
+where
+    I: IntoIterator<Item = (&'a String, &'a DependencySpec)>,
+{
+    let mut deps: Vec<_> = deps.into_iter().collect();
+    deps.sort_by(|a, b| a.0.cmp(b.0));
+
+    for name in names {
+        if !deps.iter().any(|(n, _)| *n == name) {
+            return Err(NuanceError::Other(format!(
+                "package '{name}' is not a dependency"
+            )));
+        }
+    }
+
+    let mut selected = Vec::new();
+    for (name, spec) in deps {
+        let named = names.contains(name);
+        if !names.is_empty() && !named {
+            continue;
+        }
+        let Some(url) = &spec.git else { continue };
+        if (spec.branch.is_some() || spec.rev.is_some()) && !named {
+            continue;
+        }
+        selected.push((name, spec, url.as_str()));
+    }
+
+    let repos = git::clone_or_fetch_all(selected.iter().map(|(_, _, url)| *url))?;
+    let mut plan = Plan::default();
+    for (name, spec, url) in selected {
+        if let Some(upgrade) = upgrade_spec(&repos[url], name, spec, compatible)? {
+            plan.upgrades.push(upgrade);
+        }
+        plan.relock.push(name.clone());
+    }
+    Ok(plan)
+}
+
+/// Compute the upgraded spec for one git dependency, if it changes.
+fn upgrade_spec(
+    repo_path: &Path,
+    name: &str,
+    spec: &DependencySpec,
+    compatible: bool,
+) -> Result<Option<Upgrade>> {
+    let tags = git::list_tags(repo_path)?;
+    let change = |from: &str, to: String, spec: DependencySpec| {
+        Some(Upgrade {
+            name: name.to_string(),
+            from: from.to_string(),
+            to,
+            spec,
+        })
+    };
+
+    if let Some(tag) = &spec.tag {
+        let Some(current) = version::parse_tag(tag) else {
+            return Ok(None);
+        };
+        let newest = if compatible {
+            let req = version::parse_req(name, &format!("^{current}"))?;
+            version::highest_matching(&tags, &req)
+        } else {
+            version::highest(&tags, !current.pre.is_empty())
+        };
+        return Ok(newest
+            .filter(|t| version::parse_tag(t).is_some_and(|v| v > current))
+            .and_then(|t| {
+                let spec = DependencySpec {
+                    tag: Some(t.to_string()),
+                    ..spec.clone()
+                };
+                change(tag, t.to_string(), spec)
+            }));
+    }
+
+    if let Some(req) = &spec.version {
+        // A compatible upgrade is just a re-resolve within the requirement
+        if compatible {
+            return Ok(None);
+        }
+        let parsed = version::parse_req(name, req)?;
+        let Some(newest) = version::highest(&tags, false).and_then(version::parse_tag) else {
+            return Ok(None);
+        };
+        if parsed.matches(&newest) {
+            return Ok(None);
+        }
+        let new_req = format!("^{newest}");
+        let spec = DependencySpec {
+            version: Some(new_req.clone()),
+            ..spec.clone()
+        };
+        return Ok(change(req, new_req, spec));
+    }
+
+    if let Some(rev) = &spec.rev {
+        let Some(newest) = version::highest(&tags, false) else {
+            progress::println(&format!("  {name}: no release tags to move rev {rev} to"));
+            return Ok(None);
+        };
+        let spec = DependencySpec {
+            rev: None,
+            tag: Some(newest.to_string()),
+            ..spec.clone()
+        };
+        return Ok(change(&rev[..7.min(rev.len())], newest.to_string(), spec));
+    }
+
+    // Branch dependencies keep their spec and are re-resolved to the tip
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{commit, tag, temp_dir};
+    use git2::Repository;
+
+    fn tagged_repo(dir: &Path, tags: &[&str]) {
+        let repo = Repository::init(dir).unwrap();
+        let oid = commit(&repo, &[("mod.nu", "# dep\n")]);
+        for name in tags {
+            tag(&repo, name, oid);
+        }
+    }
+
+    fn spec(key: &str, value: &str) -> DependencySpec {
+        let mut spec = DependencySpec {
+            git: Some("https://example.com/dep".to_string()),
+            ..Default::default()
+        };
+        let value = Some(value.to_string());
+        match key {
+            "tag" => spec.tag = value,
+            "version" => spec.version = value,
+            "rev" => spec.rev = value,
+            _ => spec.branch = value,
+        }
+        spec
+    }
+
+    #[test]
+    fn upgrades_tag_pins_to_newest_or_compatible_tag() {
+        let dir = temp_dir("tags");
+        tagged_repo(&dir, &["v1.0.0", "v1.4.0", "v2.1.0", "v3.0.0-rc1"]);
+
+        let upgrade = upgrade_spec(&dir, "dep", &spec("tag", "v1.0.0"), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(upgrade.from, "v1.0.0");
+        assert_eq!(upgrade.to, "v2.1.0");
+        assert_eq!(upgrade.spec.tag.as_deref(), Some("v2.1.0"));
+
+        let upgrade = upgrade_spec(&dir, "dep", &spec("tag", "v1.0.0"), true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(upgrade.to, "v1.4.0");
+
+        assert_eq!(
+            upgrade_spec(&dir, "dep", &spec("tag", "v2.1.0"), false).unwrap(),
+            None
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn widens_version_requirement_and_moves_rev_to_tag() {
+        let dir = temp_dir("version");
+        tagged_repo(&dir, &["v1.0.0", "v2.1.0"]);
+
+        let upgrade = upgrade_spec(&dir, "dep", &spec("version", "^1.0"), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(upgrade.to, "^2.1.0");
+        assert_eq!(
+            upgrade_spec(&dir, "dep", &spec("version", "^1.0"), true).unwrap(),
+            None
+        );
+
+        let upgrade = upgrade_spec(&dir, "dep", &spec("rev", "abcdef0123"), false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(upgrade.spec.rev, None);
+        assert_eq!(upgrade.spec.tag.as_deref(), Some("v2.1.0"));
+
+        assert_eq!(
+            upgrade_spec(&dir, "dep", &spec("branch", "main"), false).unwrap(),
+            None
+        );
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,126 @@
+use semver::{Version, VersionReq};
+
+use crate::error::{NuanceError, Result};
+
+/// Parse a git tag name as a semver version.
+///
+/// Accepts an optional `v`/`V` prefix and fills in missing minor/patch
+/// components, so `v1.2.3`, `1.2.3`, `v1.2` and `v1` are all recognized.
+/// Returns `None` for tags that are not versions (e.g. `nightly`).
+pub fn parse_tag(tag: &str) -> Option<Version> {
+    let stripped = tag
+        .strip_prefix('v')
+        .or_else(|| tag.strip_prefix('V'))
+        .unwrap_or(tag);
+
+    if let Ok(version) = Version::parse(stripped) {
+        return Some(version);
+    }
+
+    // Pad short versions like `1.2` or `1` (without pre-release/build parts)
+    let parts: Vec<&str> = stripped.split('.').collect();
+    if parts.len() < 3
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+    {
+        let mut padded = parts.join(".");
+        for _ in parts.len()..3 {
+            padded.push_str(".0");
+        }
+        return Version::parse(&padded).ok();
+    }
+
+    None
+}
+
+/// Parse a version requirement such as `^1.2`, `~1.2.3` or `>=0.4, <0.6`.
+pub fn parse_req(name: &str, req: &str) -> Result<VersionReq> {
+    VersionReq::parse(req).map_err(|e| {
+        NuanceError::Manifest(format!(
+            "dependency '{name}': invalid version requirement '{req}': {e}"
+        ))
+    })
+}
+
+/// Pick the tag with the highest version that satisfies `req`.
+///
+/// Tags that don't parse as versions are ignored.
+pub fn highest_matching<'a, I>(tags: I, req: &VersionReq) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    tags.into_iter()
+        .filter_map(|tag| parse_tag(tag).map(|v| (tag.as_str(), v)))
+        .filter(|(_, v)| req.matches(v))
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+        .map(|(tag, _)| tag)
+}
+
+/// Pick the tag with the highest version, skipping pre-releases unless
+/// `include_pre` is set. Tags that don't parse as versions are ignored.
+pub fn highest<'a, I>(tags: I, include_pre: bool) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    tags.into_iter()
+        .filter_map(|tag| parse_tag(tag).map(|v| (tag.as_str(), v)))
+        .filter(|(_, v)| include_pre || v.pre.is_empty())
+        .max_by(|(_, a), (_, b)| a.cmp(b))
+        .map(|(tag, _)| tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_prefixed_and_short_tags() {
+        assert_eq!(parse_tag("v1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(parse_tag("1.2.3"), Some(Version::new(1, 2, 3)));
+        assert_eq!(parse_tag("v1.2"), Some(Version::new(1, 2, 0)));
+        assert_eq!(parse_tag("v2"), Some(Version::new(2, 0, 0)));
+        assert!(parse_tag("nightly").is_none());
+        assert!(parse_tag("v1.x").is_none());
+    }
+
+    #[test]
+    fn picks_highest_matching_tag() {
+        let available = tags(&["v1.1.0", "v1.2.0", "v1.10.0", "v2.0.0", "nightly"]);
+
+        let req = parse_req("dep", "^1.2").unwrap();
+        assert_eq!(highest_matching(&available, &req), Some("v1.10.0"));
+
+        let req = parse_req("dep", ">=0.4, <0.6").unwrap();
+        assert_eq!(highest_matching(&available, &req), None);
+    }
+
+    #[test]
+    fn skips_prereleases_unless_requested() {
+        let available = tags(&["v1.2.0", "v1.3.0-rc1"]);
+
+        let req = parse_req("dep", "^1.2").unwrap();
+        assert_eq!(highest_matching(&available, &req), Some("v1.2.0"));
+
+        let req = parse_req("dep", ">=1.3.0-rc1").unwrap();
+        assert_eq!(highest_matching(&available, &req), Some("v1.3.0-rc1"));
+    }
+
+    #[test]
+    fn highest_orders_numerically() {
+        let available = tags(&["v1.9.0", "v1.10.0", "v2.0.0-rc1", "nightly"]);
+        assert_eq!(highest(&available, false), Some("v1.10.0"));
+        assert_eq!(highest(&available, true), Some("v2.0.0-rc1"));
+        assert_eq!(highest(&tags(&["nightly", "latest"]), true), None);
+    }
+
+    #[test]
+    fn rejects_invalid_requirement() {
+        let err = parse_req("dep", "not a version").unwrap_err();
+        assert!(err.to_string().contains("invalid version requirement"));
+    }
+}
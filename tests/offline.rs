@@ -0,0 +1,124 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use git2::Repository;
+
+fn temp_dir(label: &str) -> PathBuf {
+    let unique = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!(
+        "nuance_it_{}_{}_{}",
+        label,
+        std::process::id(),
+        unique
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Create a repository at `dir` with one commit per tag, returning its URL.
+fn tagged_repo(dir: &Path, tags: &[&str]) -> String {
+    let repo = Repository::init(dir).unwrap();
+    let sig = git2::Signature::now("nuance", "nuance@example.com").unwrap();
+    for tag in tags {
+        std::fs::write(dir.join("mod.nu"), format!("# {tag}\n")).unwrap();
+        let mut index = repo.index().unwrap();
+        index
+            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+            .unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<&git2::Commit> = parent.iter().collect();
+        let oid = repo
+            .commit(Some("HEAD"), &sig, &sig, tag, &tree, &parents)
+            .unwrap();
+        let obj = repo.find_object(oid, None).unwrap();
+        repo.tag_lightweight(tag, &obj, false).unwrap();
+    }
+    format!("file://{}", dir.display())
+}
+
+/// Run nuance in `project` with its home, config and cache under `home`.
+fn nuance(home: &Path, project: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_nuance"))
+        .args(args)
+        .current_dir(project)
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join(".config"))
+        .env("XDG_CACHE_HOME", home.join(".cache"))
+        .env_remove("NUANCE_OFFLINE")
+        .env_remove("NUANCE_GIT_TOKEN")
+        .output()
+        .unwrap()
+}
+
+fn write_manifest(project: &Path, deps: &str) {
+    std::fs::write(
+        project.join("mod.toml"),
+        format!("[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\n{deps}"),
+    )
+    .unwrap();
+}
+
+#[test]
+fn frozen_offline_install_works_from_a_warm_cache() {
+    let dir = temp_dir("offline_warm");
+    let home = dir.join("home");
+    let project = dir.join("app");
+    std::fs::create_dir_all(&project).unwrap();
+    let url = tagged_repo(&dir.join("nu-dep"), &["v1.0.0"]);
+    write_manifest(
+        &project,
+        &format!("nu-dep = {{ git = \"{url}\", tag = \"v1.0.0\" }}\n"),
+    );
+
+    let output = nuance(&home, &project, &["install"]);
+    assert!(output.status.success(), "{output:?}");
+
+    std::fs::remove_dir_all(project.join(".nu_modules")).unwrap();
+    // The remote is gone; only the cache can serve the install
+    std::fs::remove_dir_all(dir.join("nu-dep")).unwrap();
+    let output = nuance(&home, &project, &["--offline", "install", "--frozen"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(project.join(".nu_modules/nu-dep/mod.nu").is_file());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn offline_install_lists_everything_missing_from_the_cache() {
+    let dir = temp_dir("offline_missing");
+    let home = dir.join("home");
+    let project = dir.join("app");
+    std::fs::create_dir_all(&project).unwrap();
+    let cached = tagged_repo(&dir.join("nu-cached"), &["v1.0.0", "v2.0.0"]);
+    let uncached = tagged_repo(&dir.join("nu-uncached"), &["v1.0.0"]);
+    write_manifest(
+        &project,
+        &format!("nu-cached = {{ git = \"{cached}\", tag = \"v1.0.0\" }}\n"),
+    );
+    let output = nuance(&home, &project, &["install"]);
+    assert!(output.status.success(), "{output:?}");
+
+    // A tag the cached repo never fetched, and a repo never cloned
+    write_manifest(
+        &project,
+        &format!(
+            "nu-cached = {{ git = \"{cached}\", tag = \"v2.0.0\" }}\nnu-uncached = {{ git = \"{uncached}\", tag = \"v1.0.0\" }}\n"
+        ),
+    );
+    let output = nuance(&home, &project, &["--offline", "install"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("not in the git cache"), "{stderr}");
+    assert!(
+        stderr.contains(&format!("{cached} (tag v2.0.0)")),
+        "{stderr}"
+    );
+    assert!(stderr.contains(&uncached), "{stderr}");
+
+    let _ = std::fs::remove_dir_all(dir);
+}